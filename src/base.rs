@@ -26,7 +26,7 @@ pub type CGFloat = libc::c_double;
 #[cfg(not(target_pointer_width = "64"))]
 pub type CGFloat = libc::c_float;
 
-pub type CGError = libc::int32_t;
+pub type CGError = i32;
 
 pub const kCGImageAlphaNone: u32 = 0;
 pub const kCGImageAlphaPremultipliedLast: u32 = 1;
@@ -35,12 +35,17 @@ pub const kCGImageAlphaLast: u32 = 3;
 pub const kCGImageAlphaFirst: u32 = 4;
 pub const kCGImageAlphaNoneSkipLast: u32 = 5;
 pub const kCGImageAlphaNoneSkipFirst: u32 = 6;
+pub const kCGImageAlphaOnly: u32 = 7;
 
-pub const kCGBitmapByteOrderDefault: u32 = (0 << 12);
-pub const kCGBitmapByteOrder16Little: u32 = (1 << 12);
-pub const kCGBitmapByteOrder32Little: u32 = (2 << 12);
-pub const kCGBitmapByteOrder16Big: u32 = (3 << 12);
-pub const kCGBitmapByteOrder32Big: u32 = (4 << 12);
+pub const kCGBitmapAlphaInfoMask: u32 = 0x1F;
+pub const kCGBitmapFloatComponents: u32 = 1 << 8;
+pub const kCGBitmapByteOrderMask: u32 = 0x7000;
+
+pub const kCGBitmapByteOrderDefault: u32 = 0 << 12;
+pub const kCGBitmapByteOrder16Little: u32 = 1 << 12;
+pub const kCGBitmapByteOrder32Little: u32 = 2 << 12;
+pub const kCGBitmapByteOrder16Big: u32 = 3 << 12;
+pub const kCGBitmapByteOrder32Big: u32 = 4 << 12;
 
 #[cfg(target_endian = "big")]
 pub const kCGBitmapByteOrder16Host: u32 = kCGBitmapByteOrder16Big;
@@ -7,10 +7,42 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+#[cfg(target_os = "macos")]
 use core_foundation::base::{CFRelease, CFRetain, CFTypeID};
+#[cfg(target_os = "macos")]
 use core_foundation::string::CFStringRef;
-use foreign_types::ForeignType;
+#[cfg(target_os = "macos")]
+use foreign_types::{ForeignType, ForeignTypeRef};
+#[cfg(target_os = "macos")]
+use libc::size_t;
 
+/// The kind of color space, which determines the components of its colors.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CGColorSpaceModel {
+    Unknown = -1,
+    Monochrome = 0,
+    RGB = 1,
+    CMYK = 2,
+    Lab = 3,
+    DeviceN = 4,
+    Indexed = 5,
+    Pattern = 6,
+    XYZ = 7,
+}
+
+/// How colors outside of the gamut of the destination color space are mapped into it.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CGColorRenderingIntent {
+    Default = 0,
+    AbsoluteColorimetric = 1,
+    RelativeColorimetric = 2,
+    Perceptual = 3,
+    Saturation = 4,
+}
+
+#[cfg(target_os = "macos")]
 foreign_type! {
     #[doc(hidden)]
     type CType = ::sys::CGColorSpace;
@@ -20,6 +52,7 @@ foreign_type! {
     pub struct CGColorSpaceRef;
 }
 
+#[cfg(target_os = "macos")]
 impl CGColorSpace {
     pub fn type_id() -> CFTypeID {
         unsafe {
@@ -42,6 +75,35 @@ impl CGColorSpace {
     }
 }
 
+#[cfg(target_os = "macos")]
+impl CGColorSpaceRef {
+    /// Returns the model of the color space, or `Unknown` for models this crate doesn't know.
+    pub fn model(&self) -> CGColorSpaceModel {
+        let model = unsafe {
+            CGColorSpaceGetModel(self.as_ptr())
+        };
+        match model {
+            0 => CGColorSpaceModel::Monochrome,
+            1 => CGColorSpaceModel::RGB,
+            2 => CGColorSpaceModel::CMYK,
+            3 => CGColorSpaceModel::Lab,
+            4 => CGColorSpaceModel::DeviceN,
+            5 => CGColorSpaceModel::Indexed,
+            6 => CGColorSpaceModel::Pattern,
+            7 => CGColorSpaceModel::XYZ,
+            _ => CGColorSpaceModel::Unknown,
+        }
+    }
+
+    /// Returns the number of color components, not counting alpha.
+    pub fn number_of_components(&self) -> size_t {
+        unsafe {
+            CGColorSpaceGetNumberOfComponents(self.as_ptr())
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
 #[link(name = "CoreGraphics", kind = "framework")]
 extern {
     pub static kCGColorSpaceSRGB: CFStringRef;
@@ -55,5 +117,7 @@ extern {
     fn CGColorSpaceCreateDeviceRGB() -> ::sys::CGColorSpaceRef;
     fn CGColorSpaceCreateWithName(name: CFStringRef) -> ::sys::CGColorSpaceRef;
     fn CGColorSpaceGetTypeID() -> CFTypeID;
+    fn CGColorSpaceGetModel(space: ::sys::CGColorSpaceRef) -> i32;
+    fn CGColorSpaceGetNumberOfComponents(space: ::sys::CGColorSpaceRef) -> size_t;
 }
 
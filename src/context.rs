@@ -18,7 +18,10 @@ use std::cmp;
 use std::ptr;
 use std::slice;
 use geometry::{CGAffineTransform, CGRect};
-use image::CGImage;
+use image::{BitmapFormatError, CGBitmapInfo, CGImage};
+use path::{CGLineCap, CGLineJoin};
+use pixel::{PixelError, PixelViewMut};
+use typed_geometry::{DeviceSpace, TypedTransform, UserSpace};
 use foreign_types::ForeignType;
 
 #[repr(C)]
@@ -69,6 +72,52 @@ impl CGContext {
         }
     }
 
+    /// Creates a bitmap context drawing into `data`, or into a buffer it allocates if `data` is
+    /// `None`. A `bytes_per_row` of 0 lets Quartz choose it.
+    ///
+    /// The pixel format is checked with `CGBitmapInfo::validate`, and `bytes_per_row` against
+    /// the width, before calling the framework. `data` must point to at least
+    /// `height * bytes_per_row` bytes, which can't be checked.
+    pub fn try_create_bitmap_context(data: Option<*mut c_void>,
+                                     width: size_t,
+                                     height: size_t,
+                                     bits_per_component: size_t,
+                                     bytes_per_row: size_t,
+                                     space: &CGColorSpace,
+                                     bitmap_info: CGBitmapInfo)
+                                     -> Result<CGContext, BitmapFormatError> {
+        let bits_per_pixel = bitmap_info.validate(Some(space.model()), bits_per_component)?;
+        let minimum = width.checked_mul(bits_per_pixel)
+                           .and_then(|bits| bits.checked_add(7))
+                           .ok_or(BitmapFormatError::Overflow)? / 8;
+        if bytes_per_row != 0 {
+            if bytes_per_row < minimum {
+                return Err(BitmapFormatError::BytesPerRowTooSmall {
+                    bytes_per_row: bytes_per_row,
+                    minimum: minimum,
+                });
+            }
+            if height.checked_mul(bytes_per_row).is_none() {
+                return Err(BitmapFormatError::Overflow);
+            }
+        }
+
+        unsafe {
+            let result = CGBitmapContextCreate(data.unwrap_or(ptr::null_mut()),
+                                               width,
+                                               height,
+                                               bits_per_component,
+                                               bytes_per_row,
+                                               space.as_ptr(),
+                                               bitmap_info.bits());
+            if result.is_null() {
+                Err(BitmapFormatError::CreationFailed)
+            } else {
+                Ok(Self::from_ptr(result))
+            }
+        }
+    }
+
     pub fn data(&mut self) -> &mut [u8] {
         unsafe {
             slice::from_raw_parts_mut(
@@ -77,6 +126,20 @@ impl CGContext {
         }
     }
 
+    /// Returns a view of the rows and pixels of `data`, using the size and layout of the
+    /// bitmap, or `PixelError::NoPixelData` if this is not a bitmap context.
+    pub fn pixel_view_mut(&mut self) -> Result<PixelViewMut<'_>, PixelError> {
+        let data = unsafe {
+            CGBitmapContextGetData(self.as_ptr())
+        };
+        if data.is_null() || self.bits_per_pixel() == 0 {
+            return Err(PixelError::NoPixelData);
+        }
+        let (width, height) = (self.width(), self.height());
+        let (bytes_per_row, bits_per_pixel) = (self.bytes_per_row(), self.bits_per_pixel());
+        PixelViewMut::new(self.data(), width, height, bytes_per_row, bits_per_pixel)
+    }
+
     pub fn width(&self) -> size_t {
         unsafe {
             CGBitmapContextGetWidth(self.as_ptr())
@@ -95,6 +158,18 @@ impl CGContext {
         }
     }
 
+    pub fn bits_per_component(&self) -> size_t {
+        unsafe {
+            CGBitmapContextGetBitsPerComponent(self.as_ptr())
+        }
+    }
+
+    pub fn bits_per_pixel(&self) -> size_t {
+        unsafe {
+            CGBitmapContextGetBitsPerPixel(self.as_ptr())
+        }
+    }
+
     pub fn set_rgb_fill_color(&self, red: CGFloat, green: CGFloat, blue: CGFloat, alpha: CGFloat) {
         unsafe {
             CGContextSetRGBFillColor(self.as_ptr(), red, green, blue, alpha)
@@ -167,6 +242,30 @@ impl CGContext {
         }
     }
 
+    pub fn set_line_width(&self, width: CGFloat) {
+        unsafe {
+            CGContextSetLineWidth(self.as_ptr(), width)
+        }
+    }
+
+    pub fn set_line_cap(&self, cap: CGLineCap) {
+        unsafe {
+            CGContextSetLineCap(self.as_ptr(), cap)
+        }
+    }
+
+    pub fn set_line_join(&self, join: CGLineJoin) {
+        unsafe {
+            CGContextSetLineJoin(self.as_ptr(), join)
+        }
+    }
+
+    pub fn set_miter_limit(&self, limit: CGFloat) {
+        unsafe {
+            CGContextSetMiterLimit(self.as_ptr(), limit)
+        }
+    }
+
     pub fn draw_image(&self, rect: CGRect, image: &CGImage) {
         unsafe {
             CGContextDrawImage(self.as_ptr(), rect, image.as_ptr());
@@ -200,6 +299,14 @@ impl CGContext {
         }
     }
 
+    /// Returns the transform that maps this context's user space to its device space.
+    pub fn user_space_to_device_space_transform(&self) -> TypedTransform<UserSpace, DeviceSpace> {
+        unsafe {
+            TypedTransform::from_untyped(
+                &CGContextGetUserSpaceToDeviceSpaceTransform(self.as_ptr()))
+        }
+    }
+
     pub fn show_glyphs_at_positions(&self, glyphs: &[CGGlyph], positions: &[CGPoint]) {
         unsafe {
             let count = cmp::min(glyphs.len(), positions.len());
@@ -228,11 +335,18 @@ fn create_bitmap_context_test() {
     assert_eq!(8, img.height());
     assert_eq!(8, img.bits_per_component());
     assert_eq!(32, img.bits_per_pixel());
+    assert_eq!(img.alpha_info(), ::image::CGImageAlphaInfo::CGImageAlphaPremultipliedLast);
+    assert_eq!(img.bitmap_info().bits(), ::base::kCGImageAlphaPremultipliedLast);
+    assert_eq!(img.rendering_intent(), ::color_space::CGColorRenderingIntent::Default);
+    assert!(!img.is_mask());
     let data = img.data();
     assert_eq!(255, data.bytes()[0]);
     assert_eq!(0, data.bytes()[1]);
     assert_eq!(255, data.bytes()[2]);
     assert_eq!(255, data.bytes()[3]);
+    let view = img.pixel_view(data.bytes()).unwrap();
+    assert_eq!(view.pixel(7, 7), Some(&[255, 0, 255, 255][..]));
+    assert_eq!(view.rows().len(), 8);
 }
 
 #[link(name = "CoreGraphics", kind = "framework")]
@@ -249,6 +363,8 @@ extern {
     fn CGBitmapContextGetWidth(context: ::sys::CGContextRef) -> size_t;
     fn CGBitmapContextGetHeight(context: ::sys::CGContextRef) -> size_t;
     fn CGBitmapContextGetBytesPerRow(context: ::sys::CGContextRef) -> size_t;
+    fn CGBitmapContextGetBitsPerComponent(context: ::sys::CGContextRef) -> size_t;
+    fn CGBitmapContextGetBitsPerPixel(context: ::sys::CGContextRef) -> size_t;
     fn CGBitmapContextCreateImage(context: ::sys::CGContextRef) -> ::sys::CGImageRef;
     fn CGContextGetTypeID() -> CFTypeID;
     fn CGContextSetAllowsFontSmoothing(c: ::sys::CGContextRef, allowsFontSmoothing: bool);
@@ -272,10 +388,15 @@ extern {
                                 alpha: CGFloat);
     fn CGContextFillRect(context: ::sys::CGContextRef,
                          rect: CGRect);
+    fn CGContextSetLineWidth(c: ::sys::CGContextRef, width: CGFloat);
+    fn CGContextSetLineCap(c: ::sys::CGContextRef, cap: CGLineCap);
+    fn CGContextSetLineJoin(c: ::sys::CGContextRef, join: CGLineJoin);
+    fn CGContextSetMiterLimit(c: ::sys::CGContextRef, limit: CGFloat);
     fn CGContextDrawImage(c: ::sys::CGContextRef, rect: CGRect, image: ::sys::CGImageRef);
     fn CGContextSetFont(c: ::sys::CGContextRef, font: ::sys::CGFontRef);
     fn CGContextSetFontSize(c: ::sys::CGContextRef, size: CGFloat);
     fn CGContextSetTextMatrix(c: ::sys::CGContextRef, t: CGAffineTransform);
+    fn CGContextGetUserSpaceToDeviceSpaceTransform(c: ::sys::CGContextRef) -> CGAffineTransform;
     fn CGContextShowGlyphsAtPositions(c: ::sys::CGContextRef,
                                       glyphs: *const CGGlyph,
                                       positions: *const CGPoint,
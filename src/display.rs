@@ -16,6 +16,7 @@ pub use geometry::{CGRect, CGPoint, CGSize};
 
 use core_foundation::base::{CFRetain, TCFType};
 use image::CGImage;
+use typed_geometry::{GlobalDisplaySpace, TypedRect};
 use foreign_types::ForeignType;
 
 pub type CGDirectDisplayID = libc::uint32_t;
@@ -79,6 +80,12 @@ impl CGDisplay {
         unsafe { CGDisplayBounds(self.id) }
     }
 
+    /// Returns the bounds of a display, tagged with the global display coordinate space.
+    #[inline]
+    pub fn typed_bounds(&self) -> TypedRect<GlobalDisplaySpace> {
+        TypedRect::from_untyped(&self.bounds())
+    }
+
     /// Returns information about a display's current configuration.
     #[inline]
     pub fn display_mode(&self) -> Option<CGDisplayMode> {
@@ -3,6 +3,7 @@
 use core_foundation::base::{CFRelease, CFRetain, CFTypeID};
 use geometry::CGPoint;
 use event_source::CGEventSource;
+use typed_geometry::{GlobalDisplaySpace, TypedPoint};
 
 use libc;
 
@@ -16,6 +17,8 @@ pub type CGKeyCode = libc::uint16_t;
 /// [Ref](http://opensource.apple.com/source/IOHIDFamily/IOHIDFamily-700/IOHIDSystem/IOKit/hidsystem/IOLLEvent.h)
 bitflags! {
     #[repr(C)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
     pub struct CGEventFlags: u64 {
         const CGEventFlagNull = 0;
 
@@ -99,6 +102,7 @@ impl KeyCode {
 /// [Ref](http://opensource.apple.com/source/IOHIDFamily/IOHIDFamily-700/IOHIDSystem/IOKit/hidsystem/IOLLEvent.h)
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CGEventType {
     Null = 0,
 
@@ -361,6 +365,7 @@ impl EventField {
 // Constants that specify buttons on a one, two, or three-button mouse.
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CGMouseButton {
     Left,
     Right,
@@ -447,6 +452,11 @@ impl CGEvent {
         }
     }
 
+    /// Returns the location of the event, tagged with the global display coordinate space.
+    pub fn typed_location(&self) -> TypedPoint<GlobalDisplaySpace> {
+        TypedPoint::from_untyped(&self.location())
+    }
+
     #[cfg(feature = "elcapitan")]
     pub fn post_to_pid(&self, pid: libc::pid_t) {
         unsafe {
@@ -507,6 +517,21 @@ impl CGEvent {
     }
 }
 
+#[cfg(feature = "serde")]
+#[test]
+fn event_serde() {
+    use serde_json;
+
+    let flags = CGEventFlags::CGEventFlagShift | CGEventFlags::CGEventFlagCommand;
+    assert_eq!(serde_json::to_string(&flags).unwrap(), "1179648");
+    let back: CGEventFlags = serde_json::from_str("1179648").unwrap();
+    assert_eq!(back, flags);
+
+    assert_eq!(serde_json::to_string(&CGEventType::KeyDown).unwrap(), r#""KeyDown""#);
+    let button: CGMouseButton = serde_json::from_str(r#""Right""#).unwrap();
+    assert_eq!(button as u32, CGMouseButton::Right as u32);
+}
+
 #[link(name = "CoreGraphics", kind = "framework")]
 extern {
     /// Return the type identifier for the opaque type `CGEventRef'.
@@ -8,8 +8,14 @@
 // except according to those terms.
 
 use base::CGFloat;
+#[cfg(target_os = "macos")]
 use core_foundation::base::TCFType;
+#[cfg(target_os = "macos")]
 use core_foundation::dictionary::CFDictionary;
+use std::f64::consts::PI as PI_F64;
+use std::hash::{Hash, Hasher};
+
+const PI: CGFloat = PI_F64 as CGFloat;
 
 pub const CG_ZERO_POINT: CGPoint = CGPoint {
     x: 0.0,
@@ -26,6 +32,27 @@ pub const CG_ZERO_RECT: CGRect = CGRect {
     size: CG_ZERO_SIZE,
 };
 
+/// The null rectangle, returned e.g. by the intersection of two disjoint rectangles.
+pub const CG_RECT_NULL: CGRect = CGRect {
+    origin: CGPoint {
+        x: CGFloat::INFINITY,
+        y: CGFloat::INFINITY,
+    },
+    size: CG_ZERO_SIZE,
+};
+
+/// A rectangle with no bounds.
+pub const CG_RECT_INFINITE: CGRect = CGRect {
+    origin: CGPoint {
+        x: -CGFloat::MAX / 2.0,
+        y: -CGFloat::MAX / 2.0,
+    },
+    size: CGSize {
+        width: CGFloat::MAX,
+        height: CGFloat::MAX,
+    },
+};
+
 pub const CG_AFFINE_TRANSFORM_IDENTITY: CGAffineTransform = CGAffineTransform {
     a: 1.0, b: 0.0,
     c: 0.0, d: 1.0,
@@ -33,7 +60,8 @@ pub const CG_AFFINE_TRANSFORM_IDENTITY: CGAffineTransform = CGAffineTransform {
 };
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CGSize {
     pub width: CGFloat,
     pub height: CGFloat,
@@ -48,16 +76,22 @@ impl CGSize {
         }
     }
 
+    /// Applies `t` to this size, ignoring the translation part. Like Quartz, the intermediate
+    /// products are computed in double precision.
     #[inline]
+    #[allow(clippy::unnecessary_cast)]
     pub fn apply_transform(&self, t: &CGAffineTransform) -> CGSize {
-        unsafe {
-            ffi::CGSizeApplyAffineTransform(*self, *t)
+        let (width, height) = (self.width as f64, self.height as f64);
+        CGSize {
+            width: (t.a as f64 * width + t.c as f64 * height) as CGFloat,
+            height: (t.b as f64 * width + t.d as f64 * height) as CGFloat,
         }
     }
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CGPoint {
     pub x: CGFloat,
     pub y: CGFloat,
@@ -72,21 +106,37 @@ impl CGPoint {
         }
     }
 
+    /// Applies `t` to this point. Like Quartz, the intermediate products are computed in double
+    /// precision.
     #[inline]
+    #[allow(clippy::unnecessary_cast)]
     pub fn apply_transform(&self, t: &CGAffineTransform) -> CGPoint {
-        unsafe {
-            ffi::CGPointApplyAffineTransform(*self, *t)
+        let (x, y) = (self.x as f64, self.y as f64);
+        CGPoint {
+            x: (t.a as f64 * x + t.c as f64 * y + t.tx as f64) as CGFloat,
+            y: (t.b as f64 * x + t.d as f64 * y + t.ty as f64) as CGFloat,
         }
     }
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CGRect {
     pub origin: CGPoint,
     pub size: CGSize
 }
 
+/// The edges of a rectangle, as used by `CGRect::divide`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CGRectEdge {
+    MinXEdge = 0,
+    MinYEdge = 1,
+    MaxXEdge = 2,
+    MaxYEdge = 3,
+}
+
 impl CGRect {
     #[inline]
     pub fn new(origin: &CGPoint, size: &CGSize) -> CGRect {
@@ -96,13 +146,7 @@ impl CGRect {
         }
     }
 
-    #[inline]
-    pub fn inset(&self, size: &CGSize) -> CGRect {
-        unsafe {
-            ffi::CGRectInset(*self, size.width, size.height)
-        }
-    }
-
+    #[cfg(target_os = "macos")]
     #[inline]
     pub fn from_dict_representation(dict: &CFDictionary) -> Option<CGRect> {
         let mut rect = CGRect::new(&CGPoint::new(0., 0.), &CGSize::new(0., 0.));
@@ -116,32 +160,335 @@ impl CGRect {
         }
     }
 
+    /// Returns true if this is the null rectangle, i.e. one with an infinite origin.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        self.origin.x == CG_RECT_NULL.origin.x || self.origin.y == CG_RECT_NULL.origin.y
+    }
+
+    #[inline]
+    pub fn is_infinite(&self) -> bool {
+        self.origin.x == CG_RECT_INFINITE.origin.x &&
+            self.origin.y == CG_RECT_INFINITE.origin.y &&
+            self.size.width == CG_RECT_INFINITE.size.width &&
+            self.size.height == CG_RECT_INFINITE.size.height
+    }
+
+    /// Returns true if this rectangle is null or has a zero width or height.
     #[inline]
     pub fn is_empty(&self) -> bool {
-        unsafe {
-            // I use one, as it seems that `YES` is not available from this crate.
-            ffi::CGRectIsEmpty(*self) == 1
+        self.is_null() || self.size.width == 0.0 || self.size.height == 0.0
+    }
+
+    /// Returns an equivalent rectangle with a non-negative width and height.
+    #[inline]
+    pub fn standardize(&self) -> CGRect {
+        if self.is_null() {
+            return CG_RECT_NULL;
+        }
+
+        let mut rect = *self;
+        if rect.size.width < 0.0 {
+            rect.origin.x += rect.size.width;
+            rect.size.width = -rect.size.width;
+        }
+        if rect.size.height < 0.0 {
+            rect.origin.y += rect.size.height;
+            rect.size.height = -rect.size.height;
         }
+        rect
+    }
+
+    #[inline]
+    pub fn min_x(&self) -> CGFloat {
+        self.standardize().origin.x
+    }
+
+    #[inline]
+    pub fn mid_x(&self) -> CGFloat {
+        let rect = self.standardize();
+        rect.origin.x + rect.size.width * 0.5
+    }
+
+    #[inline]
+    pub fn max_x(&self) -> CGFloat {
+        let rect = self.standardize();
+        rect.origin.x + rect.size.width
+    }
+
+    #[inline]
+    pub fn min_y(&self) -> CGFloat {
+        self.standardize().origin.y
+    }
+
+    #[inline]
+    pub fn mid_y(&self) -> CGFloat {
+        let rect = self.standardize();
+        rect.origin.y + rect.size.height * 0.5
+    }
+
+    #[inline]
+    pub fn max_y(&self) -> CGFloat {
+        let rect = self.standardize();
+        rect.origin.y + rect.size.height
+    }
+
+    #[inline]
+    pub fn width(&self) -> CGFloat {
+        self.size.width.abs()
+    }
+
+    #[inline]
+    pub fn height(&self) -> CGFloat {
+        self.size.height.abs()
+    }
+
+    /// Returns this rectangle shrunk by `size.width` on the left and right and by `size.height`
+    /// on the top and bottom. Negative values grow the rectangle. If the result would have a
+    /// negative width or height, the null rectangle is returned.
+    pub fn inset(&self, size: &CGSize) -> CGRect {
+        if self.is_null() {
+            return CG_RECT_NULL;
+        }
+
+        let mut rect = self.standardize();
+        rect.origin.x += size.width;
+        rect.origin.y += size.height;
+        rect.size.width -= size.width * 2.0;
+        rect.size.height -= size.height * 2.0;
+        if rect.size.width < 0.0 || rect.size.height < 0.0 {
+            return CG_RECT_NULL;
+        }
+        rect
+    }
+
+    /// Returns this rectangle, standardized, with its origin moved by `(dx, dy)`.
+    #[inline]
+    pub fn offset(&self, dx: CGFloat, dy: CGFloat) -> CGRect {
+        if self.is_null() {
+            return CG_RECT_NULL;
+        }
+
+        let mut rect = self.standardize();
+        rect.origin.x += dx;
+        rect.origin.y += dy;
+        rect
+    }
+
+    /// Returns the smallest rectangle with integral coordinates that contains this rectangle.
+    pub fn integral(&self) -> CGRect {
+        if self.is_null() || self.is_infinite() {
+            return *self;
+        }
+
+        let rect = self.standardize();
+        let (min_x, min_y) = (rect.origin.x.floor(), rect.origin.y.floor());
+        let max_x = (rect.origin.x + rect.size.width).ceil();
+        let max_y = (rect.origin.y + rect.size.height).ceil();
+        CGRect::new(&CGPoint::new(min_x, min_y), &CGSize::new(max_x - min_x, max_y - min_y))
+    }
+
+    /// Returns the smallest rectangle that contains both rectangles. Null rectangles are
+    /// ignored.
+    pub fn union(&self, other: &CGRect) -> CGRect {
+        if self.is_null() {
+            return other.standardize();
+        }
+        if other.is_null() {
+            return self.standardize();
+        }
+
+        let min_x = self.min_x().min(other.min_x());
+        let min_y = self.min_y().min(other.min_y());
+        let max_x = self.max_x().max(other.max_x());
+        let max_y = self.max_y().max(other.max_y());
+        CGRect::new(&CGPoint::new(min_x, min_y), &CGSize::new(max_x - min_x, max_y - min_y))
+    }
+
+    /// Returns the overlapping area of both rectangles, or the null rectangle if they do not
+    /// overlap. Rectangles that only share an edge produce a zero-width or zero-height result.
+    pub fn intersection(&self, other: &CGRect) -> CGRect {
+        if self.is_null() || other.is_null() {
+            return CG_RECT_NULL;
+        }
+
+        let min_x = self.min_x().max(other.min_x());
+        let min_y = self.min_y().max(other.min_y());
+        let max_x = self.max_x().min(other.max_x());
+        let max_y = self.max_y().min(other.max_y());
+        if max_x < min_x || max_y < min_y {
+            return CG_RECT_NULL;
+        }
+        CGRect::new(&CGPoint::new(min_x, min_y), &CGSize::new(max_x - min_x, max_y - min_y))
     }
 
     #[inline]
     pub fn is_intersects(&self, other: &CGRect) -> bool {
-        unsafe {
-            // I use one, as it seems that `YES` is not available from this crate.
-            ffi::CGRectIntersectsRect(*self, *other) == 1
+        !self.intersection(other).is_null()
+    }
+
+    /// Splits this rectangle in two along a line parallel to `edge`, `amount` units away from
+    /// it. Returns `(slice, remainder)`, where `slice` is the part touching `edge`. `amount` is
+    /// clamped to the size of the rectangle.
+    pub fn divide(&self, amount: CGFloat, edge: CGRectEdge) -> (CGRect, CGRect) {
+        if self.is_null() {
+            return (CG_RECT_NULL, CG_RECT_NULL);
+        }
+
+        let rect = self.standardize();
+        let (mut slice, mut remainder) = (rect, rect);
+        match edge {
+            CGRectEdge::MinXEdge | CGRectEdge::MaxXEdge => {
+                let amount = amount.max(0.0).min(rect.size.width);
+                let split = if edge == CGRectEdge::MinXEdge {
+                    amount
+                } else {
+                    rect.size.width - amount
+                };
+                slice.size.width = split;
+                remainder.origin.x = rect.origin.x + split;
+                remainder.size.width = rect.size.width - split;
+            }
+            CGRectEdge::MinYEdge | CGRectEdge::MaxYEdge => {
+                let amount = amount.max(0.0).min(rect.size.height);
+                let split = if edge == CGRectEdge::MinYEdge {
+                    amount
+                } else {
+                    rect.size.height - amount
+                };
+                slice.size.height = split;
+                remainder.origin.y = rect.origin.y + split;
+                remainder.size.height = rect.size.height - split;
+            }
+        }
+
+        match edge {
+            CGRectEdge::MinXEdge | CGRectEdge::MinYEdge => (slice, remainder),
+            CGRectEdge::MaxXEdge | CGRectEdge::MaxYEdge => (remainder, slice),
         }
     }
 
+    /// Returns true if `point` lies inside this rectangle. The minimum edges are inclusive and
+    /// the maximum edges exclusive.
+    #[inline]
+    pub fn contains_point(&self, point: &CGPoint) -> bool {
+        if self.is_null() {
+            return false;
+        }
+
+        let rect = self.standardize();
+        point.x >= rect.origin.x && point.x < rect.origin.x + rect.size.width &&
+            point.y >= rect.origin.y && point.y < rect.origin.y + rect.size.height
+    }
+
+    /// Returns true if the union of both rectangles is this rectangle.
     #[inline]
+    pub fn contains_rect(&self, other: &CGRect) -> bool {
+        self.union(other).is_equal_to(self)
+    }
+
+    #[inline]
+    fn is_equal_to(&self, other: &CGRect) -> bool {
+        let (a, b) = (self.standardize(), other.standardize());
+        a.origin.x == b.origin.x && a.origin.y == b.origin.y &&
+            a.size.width == b.size.width && a.size.height == b.size.height
+    }
+
+    /// Returns the smallest rectangle containing the four transformed corners of this rectangle.
+    ///
+    /// As in Quartz, the null and infinite rectangles are returned unchanged.
     pub fn apply_transform(&self, t: &CGAffineTransform) -> CGRect {
-        unsafe {
-            ffi::CGRectApplyAffineTransform(*self, *t)
+        if self.is_null() || self.is_infinite() {
+            return *self;
         }
+
+        let rect = self.standardize();
+        let (x0, y0) = (rect.origin.x, rect.origin.y);
+        let (x1, y1) = (x0 + rect.size.width, y0 + rect.size.height);
+        let corners = [
+            CGPoint::new(x0, y0).apply_transform(t),
+            CGPoint::new(x1, y0).apply_transform(t),
+            CGPoint::new(x0, y1).apply_transform(t),
+            CGPoint::new(x1, y1).apply_transform(t),
+        ];
+
+        let (mut min_x, mut min_y) = (corners[0].x, corners[0].y);
+        let (mut max_x, mut max_y) = (min_x, min_y);
+        for corner in &corners[1..] {
+            min_x = min_x.min(corner.x);
+            min_y = min_y.min(corner.y);
+            max_x = max_x.max(corner.x);
+            max_y = max_y.max(corner.y);
+        }
+        CGRect::new(&CGPoint::new(min_x, min_y), &CGSize::new(max_x - min_x, max_y - min_y))
     }
 }
 
+#[test]
+fn rect_set_operations() {
+    let a = CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(10.0, 10.0));
+    let b = CGRect::new(&CGPoint::new(15.0, 5.0), &CGSize::new(-10.0, 10.0));
+
+    let u = a.union(&b);
+    assert_eq!((u.origin.x, u.origin.y, u.size.width, u.size.height), (0.0, 0.0, 15.0, 15.0));
+    assert!(a.union(&CG_RECT_NULL).is_equal_to(&a));
+
+    let i = a.intersection(&b);
+    assert_eq!((i.origin.x, i.origin.y, i.size.width, i.size.height), (5.0, 5.0, 5.0, 5.0));
+    assert!(a.is_intersects(&b));
+
+    let touching = CGRect::new(&CGPoint::new(10.0, 0.0), &CGSize::new(5.0, 5.0));
+    assert_eq!(a.intersection(&touching).size.width, 0.0);
+    let apart = CGRect::new(&CGPoint::new(11.0, 0.0), &CGSize::new(5.0, 5.0));
+    assert!(a.intersection(&apart).is_null());
+    assert!(!a.is_intersects(&apart));
+    assert!(!CG_RECT_NULL.is_intersects(&CG_RECT_INFINITE));
+
+    assert!(a.contains_point(&CGPoint::new(0.0, 0.0)));
+    assert!(!a.contains_point(&CGPoint::new(10.0, 5.0)));
+    assert!(a.contains_rect(&i));
+    assert!(!a.contains_rect(&b));
+    assert!(CG_RECT_INFINITE.contains_rect(&a));
+}
+
+#[test]
+fn rect_layout_operations() {
+    let r = CGRect::new(&CGPoint::new(10.0, 20.0), &CGSize::new(-4.0, -6.0));
+    let s = r.standardize();
+    assert_eq!((s.origin.x, s.origin.y, s.size.width, s.size.height), (6.0, 14.0, 4.0, 6.0));
+    assert_eq!((r.min_x(), r.mid_x(), r.max_x()), (6.0, 8.0, 10.0));
+    assert_eq!((r.min_y(), r.mid_y(), r.max_y()), (14.0, 17.0, 20.0));
+    assert_eq!((r.width(), r.height()), (4.0, 6.0));
+
+    let o = r.offset(1.0, -1.0);
+    assert_eq!((o.origin.x, o.origin.y), (7.0, 13.0));
+    assert!(CG_RECT_NULL.offset(1.0, 1.0).is_null());
+
+    let i = s.inset(&CGSize::new(1.0, 2.0));
+    assert_eq!((i.origin.x, i.origin.y, i.size.width, i.size.height), (7.0, 16.0, 2.0, 2.0));
+    assert!(s.inset(&CGSize::new(3.0, 0.0)).is_null());
+
+    let n = CGRect::new(&CGPoint::new(0.5, -0.5), &CGSize::new(1.0, 1.25)).integral();
+    assert_eq!((n.origin.x, n.origin.y, n.size.width, n.size.height), (0.0, -1.0, 2.0, 2.0));
+
+    let (slice, remainder) = s.divide(1.0, CGRectEdge::MaxXEdge);
+    assert_eq!((slice.origin.x, slice.size.width), (9.0, 1.0));
+    assert_eq!((remainder.origin.x, remainder.size.width), (6.0, 3.0));
+    let (slice, remainder) = s.divide(10.0, CGRectEdge::MinYEdge);
+    assert_eq!((slice.origin.y, slice.size.height), (14.0, 6.0));
+    assert_eq!((remainder.origin.y, remainder.size.height), (20.0, 0.0));
+
+    assert!(CG_RECT_NULL.is_empty());
+    assert!(CG_ZERO_RECT.is_empty());
+    assert!(!CG_ZERO_RECT.is_null());
+    assert!(!CG_RECT_INFINITE.is_empty());
+    assert!(CG_RECT_INFINITE.apply_transform(&CGAffineTransform::make_scale(2.0, 2.0))
+                            .is_infinite());
+}
+
 #[repr(C)]
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CGAffineTransform {
     pub a: CGFloat,
     pub b: CGFloat,
@@ -164,14 +511,643 @@ impl CGAffineTransform {
         CGAffineTransform { a, b, c, d, tx, ty }
     }
 
+    /// Returns a transform that translates by `(tx, ty)`.
+    #[inline]
+    pub fn make_translation(tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+        CGAffineTransform::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+    }
+
+    /// Returns a transform that scales by `(sx, sy)`.
+    #[inline]
+    pub fn make_scale(sx: CGFloat, sy: CGFloat) -> CGAffineTransform {
+        CGAffineTransform::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// Returns a transform that rotates by `angle` radians. Positive angles rotate
+    /// counterclockwise in the default (y-up) coordinate system.
+    #[inline]
+    pub fn make_rotation(angle: CGFloat) -> CGAffineTransform {
+        let (sin, cos) = (angle.sin(), angle.cos());
+        CGAffineTransform::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Returns this transform followed by `other`, i.e. the matrix product `self * other`.
+    ///
+    /// This matches `CGAffineTransformConcat(self, other)`.
+    #[inline]
+    pub fn concat(&self, other: &CGAffineTransform) -> CGAffineTransform {
+        CGAffineTransform {
+            a: self.a * other.a + self.b * other.c,
+            b: self.a * other.b + self.b * other.d,
+            c: self.c * other.a + self.d * other.c,
+            d: self.c * other.b + self.d * other.d,
+            tx: self.tx * other.a + self.ty * other.c + other.tx,
+            ty: self.tx * other.b + self.ty * other.d + other.ty,
+        }
+    }
+
+    /// Returns this transform preceded by a translation of `(tx, ty)`.
+    #[inline]
+    pub fn translate(&self, tx: CGFloat, ty: CGFloat) -> CGAffineTransform {
+        CGAffineTransform::make_translation(tx, ty).concat(self)
+    }
+
+    /// Returns this transform preceded by a scale of `(sx, sy)`.
+    #[inline]
+    pub fn scale(&self, sx: CGFloat, sy: CGFloat) -> CGAffineTransform {
+        CGAffineTransform::make_scale(sx, sy).concat(self)
+    }
+
+    /// Returns this transform preceded by a rotation of `angle` radians.
+    #[inline]
+    pub fn rotate(&self, angle: CGFloat) -> CGAffineTransform {
+        CGAffineTransform::make_rotation(angle).concat(self)
+    }
+
+    #[inline]
+    pub fn determinant(&self) -> CGFloat {
+        self.a * self.d - self.b * self.c
+    }
+
+    #[inline]
+    pub fn is_identity(&self) -> bool {
+        self.a == 1.0 && self.b == 0.0 &&
+            self.c == 0.0 && self.d == 1.0 &&
+            self.tx == 0.0 && self.ty == 0.0
+    }
+
+    /// Returns true if this transform has a finite, nonzero determinant.
+    #[inline]
+    pub fn is_invertible(&self) -> bool {
+        let det = self.determinant();
+        det != 0.0 && det.is_finite()
+    }
+
+    /// Returns the inverse of this transform, or `None` if it is not invertible.
+    pub fn checked_invert(&self) -> Option<CGAffineTransform> {
+        if !self.is_invertible() {
+            return None;
+        }
+
+        let det = self.determinant();
+        Some(CGAffineTransform {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            tx: (self.c * self.ty - self.d * self.tx) / det,
+            ty: (self.b * self.tx - self.a * self.ty) / det,
+        })
+    }
+
+    /// Returns the inverse of this transform. As with `CGAffineTransformInvert`, a transform that
+    /// cannot be inverted is returned unchanged.
     #[inline]
     pub fn invert(&self) -> CGAffineTransform {
-        unsafe {
-            ffi::CGAffineTransformInvert(*self)
+        self.checked_invert().unwrap_or(*self)
+    }
+}
+
+/// An affine transform broken down into simpler operations, as produced by
+/// `CGAffineTransform::decompose`.
+///
+/// The transform is rebuilt by scaling, then applying the horizontal shear (`x += shear * y`),
+/// then rotating, then translating.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CGAffineTransformComponents {
+    pub scale: CGSize,
+    pub horizontal_shear: CGFloat,
+    /// The rotation in radians, in the range `-π..=π`.
+    pub rotation: CGFloat,
+    pub translation: CGPoint,
+}
+
+impl CGAffineTransform {
+    /// Splits this transform into scale, shear, rotation and translation.
+    ///
+    /// A reflection is expressed as a negative scale along whichever axis keeps the rotation
+    /// smallest, so that e.g. a horizontal flip decomposes without any rotation.
+    ///
+    /// The components cannot express a singular transform whose second row `(c, d)` is a nonzero
+    /// multiple of a nonzero first row `(a, b)`, since a zero vertical scale also zeroes the
+    /// second row. Such a transform decomposes with a zero vertical scale and no shear, so it
+    /// recomposes with `c` and `d` both zero.
+    pub fn decompose(&self) -> CGAffineTransformComponents {
+        let mut scale_x = (self.a * self.a + self.b * self.b).sqrt();
+        if self.determinant() < 0.0 && self.a < self.d {
+            scale_x = -scale_x;
+        }
+
+        // If the first row is zero, take the rotation from the second one instead so that no
+        // shear is needed.
+        let rotation = if scale_x != 0.0 {
+            (self.b / scale_x).atan2(self.a / scale_x)
+        } else {
+            (-self.c).atan2(self.d)
+        };
+        let (sin, cos) = (rotation.sin(), rotation.cos());
+
+        // Undo the rotation on the second row, leaving `(scale_y * shear, scale_y)`.
+        let scale_y = self.d * cos - self.c * sin;
+        let sheared = self.c * cos + self.d * sin;
+        let horizontal_shear = if scale_y != 0.0 { sheared / scale_y } else { 0.0 };
+
+        CGAffineTransformComponents {
+            scale: CGSize::new(scale_x, scale_y),
+            horizontal_shear: horizontal_shear,
+            rotation: rotation,
+            translation: CGPoint::new(self.tx, self.ty),
+        }
+    }
+
+    /// Rebuilds a transform from its components. This is the inverse of `decompose`, except for
+    /// the singular transforms it cannot express.
+    pub fn make_with_components(components: &CGAffineTransformComponents)
+                                -> CGAffineTransform {
+        let (sin, cos) = (components.rotation.sin(), components.rotation.cos());
+        let (scale_x, scale_y) = (components.scale.width, components.scale.height);
+        let shear = components.horizontal_shear;
+        CGAffineTransform {
+            a: scale_x * cos,
+            b: scale_x * sin,
+            c: scale_y * (shear * cos - sin),
+            d: scale_y * (shear * sin + cos),
+            tx: components.translation.x,
+            ty: components.translation.y,
+        }
+    }
+
+    /// Interpolates between this transform (at `t == 0`) and `other` (at `t == 1`) by
+    /// interpolating their decomposed components.
+    ///
+    /// Rotation takes the shortest path, and a pair of opposite single-axis reflections is
+    /// treated as a rotation by π, matching the behavior of Core Animation.
+    pub fn interpolate(&self, other: &CGAffineTransform, t: CGFloat) -> CGAffineTransform {
+        let mut from = self.decompose();
+        let to = other.decompose();
+
+        if (from.scale.width < 0.0 && to.scale.height < 0.0) ||
+                (from.scale.height < 0.0 && to.scale.width < 0.0) {
+            from.scale.width = -from.scale.width;
+            from.scale.height = -from.scale.height;
+            from.rotation += if from.rotation < 0.0 { PI } else { -PI };
+        }
+
+        let mut delta = to.rotation - from.rotation;
+        if delta > PI {
+            delta -= 2.0 * PI;
+        } else if delta < -PI {
+            delta += 2.0 * PI;
         }
+
+        let lerp = |a: CGFloat, b: CGFloat| a + (b - a) * t;
+        CGAffineTransform::make_with_components(&CGAffineTransformComponents {
+            scale: CGSize::new(lerp(from.scale.width, to.scale.width),
+                               lerp(from.scale.height, to.scale.height)),
+            horizontal_shear: lerp(from.horizontal_shear, to.horizontal_shear),
+            rotation: from.rotation + delta * t,
+            translation: CGPoint::new(lerp(from.translation.x, to.translation.x),
+                                      lerp(from.translation.y, to.translation.y)),
+        })
+    }
+}
+
+/// The default tolerance used by `ApproxEq::approx_eq`.
+pub const CG_APPROX_EQ_EPSILON: CGFloat = 1e-6;
+
+/// Approximate equality for `CGFloat` and the geometry types built from it.
+///
+/// Compound types compare equal if every component does.
+pub trait ApproxEq {
+    /// Returns true if every component differs by at most `epsilon`.
+    fn approx_eq_eps(&self, other: &Self, epsilon: CGFloat) -> bool;
+
+    /// Returns true if every component is at most `max_ulps` representable values away from the
+    /// corresponding one in `other`. This scales with magnitude, unlike `approx_eq_eps`.
+    fn approx_eq_ulps(&self, other: &Self, max_ulps: u32) -> bool;
+
+    #[inline]
+    fn approx_eq(&self, other: &Self) -> bool {
+        self.approx_eq_eps(other, CG_APPROX_EQ_EPSILON)
     }
 }
 
+/// Maps the bits of a float to an integer such that adjacent floats map to adjacent integers.
+#[cfg(target_pointer_width = "64")]
+#[inline]
+fn ordered_bits(value: CGFloat) -> i64 {
+    let bits = value.to_bits() as i64;
+    if bits < 0 { i64::MIN - bits } else { bits }
+}
+
+#[cfg(not(target_pointer_width = "64"))]
+#[inline]
+fn ordered_bits(value: CGFloat) -> i64 {
+    let bits = value.to_bits() as i32;
+    (if bits < 0 { i32::MIN - bits } else { bits }) as i64
+}
+
+impl ApproxEq for CGFloat {
+    #[inline]
+    fn approx_eq_eps(&self, other: &CGFloat, epsilon: CGFloat) -> bool {
+        (*self - *other).abs() <= epsilon
+    }
+
+    #[inline]
+    fn approx_eq_ulps(&self, other: &CGFloat, max_ulps: u32) -> bool {
+        if self.is_nan() || other.is_nan() {
+            return false;
+        }
+        let distance = ordered_bits(*self) as i128 - ordered_bits(*other) as i128;
+        distance.abs() <= max_ulps as i128
+    }
+}
+
+impl ApproxEq for CGPoint {
+    #[inline]
+    fn approx_eq_eps(&self, other: &CGPoint, epsilon: CGFloat) -> bool {
+        self.x.approx_eq_eps(&other.x, epsilon) && self.y.approx_eq_eps(&other.y, epsilon)
+    }
+
+    #[inline]
+    fn approx_eq_ulps(&self, other: &CGPoint, max_ulps: u32) -> bool {
+        self.x.approx_eq_ulps(&other.x, max_ulps) && self.y.approx_eq_ulps(&other.y, max_ulps)
+    }
+}
+
+impl ApproxEq for CGSize {
+    #[inline]
+    fn approx_eq_eps(&self, other: &CGSize, epsilon: CGFloat) -> bool {
+        self.width.approx_eq_eps(&other.width, epsilon) &&
+            self.height.approx_eq_eps(&other.height, epsilon)
+    }
+
+    #[inline]
+    fn approx_eq_ulps(&self, other: &CGSize, max_ulps: u32) -> bool {
+        self.width.approx_eq_ulps(&other.width, max_ulps) &&
+            self.height.approx_eq_ulps(&other.height, max_ulps)
+    }
+}
+
+impl ApproxEq for CGRect {
+    #[inline]
+    fn approx_eq_eps(&self, other: &CGRect, epsilon: CGFloat) -> bool {
+        self.origin.approx_eq_eps(&other.origin, epsilon) &&
+            self.size.approx_eq_eps(&other.size, epsilon)
+    }
+
+    #[inline]
+    fn approx_eq_ulps(&self, other: &CGRect, max_ulps: u32) -> bool {
+        self.origin.approx_eq_ulps(&other.origin, max_ulps) &&
+            self.size.approx_eq_ulps(&other.size, max_ulps)
+    }
+}
+
+impl ApproxEq for CGAffineTransform {
+    fn approx_eq_eps(&self, other: &CGAffineTransform, epsilon: CGFloat) -> bool {
+        self.a.approx_eq_eps(&other.a, epsilon) && self.b.approx_eq_eps(&other.b, epsilon) &&
+            self.c.approx_eq_eps(&other.c, epsilon) && self.d.approx_eq_eps(&other.d, epsilon) &&
+            self.tx.approx_eq_eps(&other.tx, epsilon) && self.ty.approx_eq_eps(&other.ty, epsilon)
+    }
+
+    fn approx_eq_ulps(&self, other: &CGAffineTransform, max_ulps: u32) -> bool {
+        self.a.approx_eq_ulps(&other.a, max_ulps) && self.b.approx_eq_ulps(&other.b, max_ulps) &&
+            self.c.approx_eq_ulps(&other.c, max_ulps) &&
+            self.d.approx_eq_ulps(&other.d, max_ulps) &&
+            self.tx.approx_eq_ulps(&other.tx, max_ulps) &&
+            self.ty.approx_eq_ulps(&other.ty, max_ulps)
+    }
+}
+
+/// Geometry that can be compared and hashed by the bit patterns of its components. See
+/// `BitKey`.
+pub trait BitPattern {
+    fn hash_bits<H: Hasher>(&self, state: &mut H);
+    fn eq_bits(&self, other: &Self) -> bool;
+}
+
+impl BitPattern for CGFloat {
+    #[inline]
+    fn hash_bits<H: Hasher>(&self, state: &mut H) {
+        canonical_bits(*self).hash(state)
+    }
+
+    #[inline]
+    fn eq_bits(&self, other: &CGFloat) -> bool {
+        canonical_bits(*self) == canonical_bits(*other)
+    }
+}
+
+/// Returns the bits of `value`, with `-0.0` mapped to `0.0` and all NaNs mapped to one pattern,
+/// so that values which are `==` have the same bits and every NaN is equal to itself.
+// The casts widen the bits of an `f32` `CGFloat` on 32-bit targets.
+#[inline]
+#[allow(clippy::unnecessary_cast)]
+fn canonical_bits(value: CGFloat) -> u64 {
+    if value == 0.0 {
+        0
+    } else if value.is_nan() {
+        CGFloat::NAN.to_bits() as u64
+    } else {
+        value.to_bits() as u64
+    }
+}
+
+impl BitPattern for CGPoint {
+    #[inline]
+    fn hash_bits<H: Hasher>(&self, state: &mut H) {
+        self.x.hash_bits(state);
+        self.y.hash_bits(state);
+    }
+
+    #[inline]
+    fn eq_bits(&self, other: &CGPoint) -> bool {
+        self.x.eq_bits(&other.x) && self.y.eq_bits(&other.y)
+    }
+}
+
+impl BitPattern for CGSize {
+    #[inline]
+    fn hash_bits<H: Hasher>(&self, state: &mut H) {
+        self.width.hash_bits(state);
+        self.height.hash_bits(state);
+    }
+
+    #[inline]
+    fn eq_bits(&self, other: &CGSize) -> bool {
+        self.width.eq_bits(&other.width) && self.height.eq_bits(&other.height)
+    }
+}
+
+impl BitPattern for CGRect {
+    #[inline]
+    fn hash_bits<H: Hasher>(&self, state: &mut H) {
+        self.origin.hash_bits(state);
+        self.size.hash_bits(state);
+    }
+
+    #[inline]
+    fn eq_bits(&self, other: &CGRect) -> bool {
+        self.origin.eq_bits(&other.origin) && self.size.eq_bits(&other.size)
+    }
+}
+
+impl BitPattern for CGAffineTransform {
+    fn hash_bits<H: Hasher>(&self, state: &mut H) {
+        for component in &[self.a, self.b, self.c, self.d, self.tx, self.ty] {
+            component.hash_bits(state);
+        }
+    }
+
+    fn eq_bits(&self, other: &CGAffineTransform) -> bool {
+        self.a.eq_bits(&other.a) && self.b.eq_bits(&other.b) &&
+            self.c.eq_bits(&other.c) && self.d.eq_bits(&other.d) &&
+            self.tx.eq_bits(&other.tx) && self.ty.eq_bits(&other.ty)
+    }
+}
+
+/// A wrapper that implements `Eq` and `Hash` by comparing bit patterns, so that rects,
+/// transforms and the like can be used as `HashMap` keys.
+///
+/// Zeroes of either sign compare equal, as do all NaNs.
+#[derive(Clone, Copy, Debug)]
+pub struct BitKey<T>(pub T);
+
+impl<T: BitPattern> PartialEq for BitKey<T> {
+    #[inline]
+    fn eq(&self, other: &BitKey<T>) -> bool {
+        self.0.eq_bits(&other.0)
+    }
+}
+
+impl<T: BitPattern> Eq for BitKey<T> {}
+
+impl<T: BitPattern> Hash for BitKey<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash_bits(state)
+    }
+}
+
+#[test]
+fn affine_transform_algebra() {
+    let t = CGAffineTransform::make_translation(10.0, 20.0).scale(2.0, 4.0);
+    let p = CGPoint::new(1.0, 1.0).apply_transform(&t);
+    assert_eq!((p.x, p.y), (12.0, 24.0));
+    assert_eq!(t.determinant(), 8.0);
+
+    let s = CGSize::new(1.0, 1.0).apply_transform(&t);
+    assert_eq!((s.width, s.height), (2.0, 4.0));
+
+    let r = CGRect::new(&CGPoint::new(1.0, 1.0), &CGSize::new(-2.0, 1.0)).apply_transform(&t);
+    assert_eq!((r.origin.x, r.origin.y, r.size.width, r.size.height), (8.0, 24.0, 4.0, 4.0));
+
+    let identity = t.concat(&t.invert());
+    assert!(identity.is_identity());
+
+    let singular = CGAffineTransform::make_scale(0.0, 1.0);
+    assert!(!singular.is_invertible());
+    assert!(singular.checked_invert().is_none());
+    assert_eq!(singular.invert().a, 0.0);
+
+    let r = CGAffineTransform::make_rotation(::std::f64::consts::FRAC_PI_2 as CGFloat);
+    let p = CGPoint::new(1.0, 0.0).apply_transform(&r);
+    assert!(p.x.abs() < 1e-6 && (p.y - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn affine_transform_decomposition() {
+    let t = CGAffineTransform::make_rotation(0.5).scale(2.0, 3.0).translate(4.0, 5.0);
+    let components = t.decompose();
+    assert!((components.scale.width - 2.0).abs() < 1e-12);
+    assert!((components.scale.height - 3.0).abs() < 1e-12);
+    assert!(components.horizontal_shear.abs() < 1e-12);
+    assert!((components.rotation - 0.5).abs() < 1e-12);
+
+    let flip = CGAffineTransform::make_scale(-1.0, 1.0).decompose();
+    assert_eq!((flip.scale.width, flip.scale.height, flip.rotation), (-1.0, 1.0, 0.0));
+
+    // Decomposing and recomposing must round-trip for a spread of transforms, including
+    // reflections, shears and degenerate ones.
+    let mut seed = 0x2545_f491u32;
+    let mut next = || {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        ((seed >> 8) as CGFloat / (1 << 24) as CGFloat) * 8.0 - 4.0
+    };
+    for i in 0..1000 {
+        let mut t = CGAffineTransform::new(next(), next(), next(), next(), next(), next());
+        if i % 10 == 0 {
+            t.a = 0.0;
+            t.b = 0.0;
+        }
+        if i % 20 == 0 {
+            t.d = 0.0;
+        }
+        let r = CGAffineTransform::make_with_components(&t.decompose());
+        for &(expected, actual) in &[(t.a, r.a), (t.b, r.b), (t.c, r.c),
+                                     (t.d, r.d), (t.tx, r.tx), (t.ty, r.ty)] {
+            assert!((expected - actual).abs() < 1e-9, "{:?} became {:?}", t, r);
+        }
+    }
+
+    // A second row parallel to a nonzero first row is lost.
+    let singular = CGAffineTransform::new(1.0, 0.0, 1.0, 0.0, 0.0, 0.0);
+    let components = singular.decompose();
+    assert_eq!((components.scale, components.horizontal_shear, components.rotation),
+               (CGSize::new(1.0, 0.0), 0.0, 0.0));
+    assert_eq!(CGAffineTransform::make_with_components(&components),
+               CGAffineTransform::new(1.0, 0.0, 0.0, 0.0, 0.0, 0.0));
+}
+
+#[test]
+fn affine_transform_interpolation() {
+    let from = CGAffineTransform::make_rotation(170.0 * PI / 180.0);
+    let to = CGAffineTransform::make_rotation(-170.0 * PI / 180.0);
+    let mid = from.interpolate(&to, 0.5).decompose();
+    assert!((mid.rotation.abs() - PI).abs() < 1e-9);
+
+    let from = CGAffineTransform::make_translation(0.0, 0.0);
+    let to = CGAffineTransform::make_translation(10.0, -4.0).scale(3.0, 3.0);
+    let mid = from.interpolate(&to, 0.5);
+    assert!((mid.a - 2.0).abs() < 1e-12 && (mid.d - 2.0).abs() < 1e-12);
+    assert!((mid.tx - 5.0).abs() < 1e-12 && (mid.ty + 2.0).abs() < 1e-12);
+
+    // A horizontal flip is a vertical flip turned by π, so interpolating between them rotates
+    // instead of collapsing the scale through zero.
+    let from = CGAffineTransform::make_scale(-1.0, 1.0);
+    let to = CGAffineTransform::make_scale(1.0, -1.0);
+    let mid = from.interpolate(&to, 0.5);
+    assert!((mid.determinant() + 1.0).abs() < 1e-9);
+    assert!(mid.a.abs() < 1e-9 && (mid.b.abs() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn geometry_approx_eq() {
+    let t = CGAffineTransform::make_rotation(0.7).scale(3.0, 0.5);
+    assert!(t.concat(&t.invert()).approx_eq(&CG_AFFINE_TRANSFORM_IDENTITY));
+    assert!(t != t.concat(&CG_AFFINE_TRANSFORM_IDENTITY.translate(1e-9, 0.0)));
+    assert!(t.approx_eq(&t.concat(&CG_AFFINE_TRANSFORM_IDENTITY.translate(1e-9, 0.0))));
+
+    let a = CGPoint::new(1e12, -0.0);
+    let b = CGPoint::new(1e12 + 1e-3, 0.0);
+    assert!(!a.approx_eq(&b));
+    assert!(a.approx_eq_ulps(&b, 16));
+    assert!(!a.approx_eq_ulps(&CGPoint::new(1e12 + 1.0, 0.0), 16));
+    assert!(!CGFloat::NAN.approx_eq_ulps(&CGFloat::NAN, 16));
+
+    let rect = CGRect::new(&a, &CGSize::new(1.0, 2.0));
+    assert_eq!(rect, rect);
+    assert!(rect.approx_eq_eps(&CGRect::new(&CGPoint::new(1e12, 0.1), &rect.size), 0.1));
+}
+
+#[test]
+fn geometry_bit_key() {
+    use std::collections::HashMap;
+
+    let mut cache = HashMap::new();
+    let rect = CGRect::new(&CGPoint::new(0.0, 1.5), &CGSize::new(10.0, 20.0));
+    cache.insert(BitKey(rect), "rect");
+    let negative_zero = CGRect::new(&CGPoint::new(-0.0, 1.5), &rect.size);
+    assert_eq!(cache.get(&BitKey(negative_zero)), Some(&"rect"));
+    assert!(!cache.contains_key(&BitKey(rect.offset(1e-9, 0.0))));
+
+    let nan = CGAffineTransform::make_scale(CGFloat::NAN, 1.0);
+    assert!(nan != nan);
+    assert!(BitKey(nan) == BitKey(nan));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn geometry_serde() {
+    use serde_json;
+
+    let rect = CGRect::new(&CGPoint::new(1.0, 2.0), &CGSize::new(3.0, 4.0));
+    let json = serde_json::to_string(&rect).unwrap();
+    assert_eq!(json, r#"{"origin":{"x":1.0,"y":2.0},"size":{"width":3.0,"height":4.0}}"#);
+    let back: CGRect = serde_json::from_str(&json).unwrap();
+    assert_eq!((back.origin.x, back.origin.y, back.size.width, back.size.height),
+               (1.0, 2.0, 3.0, 4.0));
+
+    let t = CGAffineTransform::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+    let json = serde_json::to_string(&t).unwrap();
+    assert_eq!(json, r#"{"a":1.0,"b":2.0,"c":3.0,"d":4.0,"tx":5.0,"ty":6.0}"#);
+    let back: CGAffineTransform = serde_json::from_str(&json).unwrap();
+    assert_eq!((back.a, back.b, back.c, back.d, back.tx, back.ty),
+               (1.0, 2.0, 3.0, 4.0, 5.0, 6.0));
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn affine_transform_matches_quartz() {
+    let t = CGAffineTransform::make_rotation(0.3).scale(1.5, -0.25).translate(7.0, -3.0);
+    let p = CGPoint::new(3.25, -1.5);
+    let s = CGSize::new(4.0, 9.5);
+    let r = CGRect::new(&p, &s);
+    unsafe {
+        let expected = ffi::CGPointApplyAffineTransform(p, t);
+        let actual = p.apply_transform(&t);
+        assert_eq!((actual.x, actual.y), (expected.x, expected.y));
+
+        let expected = ffi::CGSizeApplyAffineTransform(s, t);
+        let actual = s.apply_transform(&t);
+        assert_eq!((actual.width, actual.height), (expected.width, expected.height));
+
+        let expected = ffi::CGRectApplyAffineTransform(r, t);
+        let actual = r.apply_transform(&t);
+        assert_eq!((actual.origin.x, actual.origin.y), (expected.origin.x, expected.origin.y));
+        assert_eq!((actual.size.width, actual.size.height),
+                   (expected.size.width, expected.size.height));
+
+        let expected = ffi::CGAffineTransformInvert(t);
+        let actual = t.invert();
+        for &(e, a) in &[(expected.a, actual.a), (expected.b, actual.b),
+                         (expected.c, actual.c), (expected.d, actual.d),
+                         (expected.tx, actual.tx), (expected.ty, actual.ty)] {
+            assert!((e - a).abs() <= 1e-9 * e.abs().max(1.0));
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn rect_operations_match_quartz() {
+    let rects = [
+        CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(10.0, 10.0)),
+        CGRect::new(&CGPoint::new(15.0, 5.5), &CGSize::new(-10.0, 10.0)),
+        CGRect::new(&CGPoint::new(10.0, 0.0), &CGSize::new(5.0, 5.0)),
+        CGRect::new(&CGPoint::new(-3.25, 2.0), &CGSize::new(0.0, -4.75)),
+        CG_ZERO_RECT,
+        CG_RECT_NULL,
+        CG_RECT_INFINITE,
+    ];
+    let same = |a: CGRect, b: CGRect| {
+        (a.is_null() && b.is_null()) ||
+            (a.origin.x == b.origin.x && a.origin.y == b.origin.y &&
+             a.size.width == b.size.width && a.size.height == b.size.height)
+    };
+    let edges = [CGRectEdge::MinXEdge, CGRectEdge::MinYEdge,
+                 CGRectEdge::MaxXEdge, CGRectEdge::MaxYEdge];
+    unsafe {
+        for a in &rects {
+            assert_eq!(a.is_empty(), ffi::CGRectIsEmpty(*a) != 0);
+            assert!(same(a.integral(), ffi::CGRectIntegral(*a)));
+            assert!(same(a.inset(&CGSize::new(1.5, -2.0)), ffi::CGRectInset(*a, 1.5, -2.0)));
+            for &edge in &edges {
+                let (mut slice, mut remainder) = (CG_ZERO_RECT, CG_ZERO_RECT);
+                ffi::CGRectDivide(*a, &mut slice, &mut remainder, 3.0, edge);
+                let (s, r) = a.divide(3.0, edge);
+                assert!(same(s, slice) && same(r, remainder));
+            }
+            for b in &rects {
+                assert!(same(a.union(b), ffi::CGRectUnion(*a, *b)));
+                assert!(same(a.intersection(b), ffi::CGRectIntersection(*a, *b)));
+                assert_eq!(a.is_intersects(b), ffi::CGRectIntersectsRect(*a, *b) != 0);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
 mod ffi {
     use base::{CGFloat, boolean_t};
     use geometry::{CGAffineTransform, CGPoint, CGRect, CGSize};
@@ -179,16 +1155,37 @@ mod ffi {
 
     #[link(name = "CoreGraphics", kind = "framework")]
     extern {
-        pub fn CGRectInset(rect: CGRect, dx: CGFloat, dy: CGFloat) -> CGRect;
         pub fn CGRectMakeWithDictionaryRepresentation(dict: CFDictionaryRef,
                                                       rect: *mut CGRect) -> boolean_t;
+
+        // The following are reimplemented in Rust above and are only used to cross-check the
+        // results against Quartz.
+        #[cfg(all(test, target_os = "macos"))]
+        pub fn CGRectInset(rect: CGRect, dx: CGFloat, dy: CGFloat) -> CGRect;
+        #[cfg(all(test, target_os = "macos"))]
         pub fn CGRectIsEmpty(rect: CGRect) -> boolean_t;
+        #[cfg(all(test, target_os = "macos"))]
         pub fn CGRectIntersectsRect(rect1: CGRect, rect2: CGRect) -> boolean_t;
-
+        #[cfg(all(test, target_os = "macos"))]
+        pub fn CGRectUnion(r1: CGRect, r2: CGRect) -> CGRect;
+        #[cfg(all(test, target_os = "macos"))]
+        pub fn CGRectIntersection(r1: CGRect, r2: CGRect) -> CGRect;
+        #[cfg(all(test, target_os = "macos"))]
+        pub fn CGRectIntegral(rect: CGRect) -> CGRect;
+        #[cfg(all(test, target_os = "macos"))]
+        pub fn CGRectDivide(rect: CGRect,
+                            slice: *mut CGRect,
+                            remainder: *mut CGRect,
+                            amount: CGFloat,
+                            edge: ::geometry::CGRectEdge);
+        #[cfg(all(test, target_os = "macos"))]
         pub fn CGAffineTransformInvert(t: CGAffineTransform) -> CGAffineTransform;
 
+        #[cfg(all(test, target_os = "macos"))]
         pub fn CGPointApplyAffineTransform(point: CGPoint, t: CGAffineTransform) -> CGPoint;
+        #[cfg(all(test, target_os = "macos"))]
         pub fn CGRectApplyAffineTransform(rect: CGRect, t: CGAffineTransform) -> CGRect;
+        #[cfg(all(test, target_os = "macos"))]
         pub fn CGSizeApplyAffineTransform(size: CGSize, t: CGAffineTransform) -> CGSize;
     }
 }
@@ -1,11 +1,30 @@
+use base::{kCGBitmapAlphaInfoMask, kCGBitmapByteOrderMask, kCGBitmapFloatComponents};
+#[cfg(target_os = "macos")]
+use base::CGFloat;
+#[cfg(target_os = "macos")]
 use core_foundation::base::{CFRetain, CFTypeID};
+#[cfg(target_os = "macos")]
 use core_foundation::data::CFData;
-use color_space::CGColorSpace;
-use data_provider::CGDataProviderRef;
+use color_space::CGColorSpaceModel;
+#[cfg(target_os = "macos")]
+use color_space::{CGColorRenderingIntent, CGColorSpace};
+#[cfg(target_os = "macos")]
+use data_provider::{CGDataProvider, CGDataProviderRef};
+#[cfg(target_os = "macos")]
 use libc::size_t;
+#[cfg(target_os = "macos")]
 use foreign_types::{ForeignType, ForeignTypeRef};
+#[cfg(target_os = "macos")]
+use pixel::{PixelError, PixelView};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+#[cfg(target_os = "macos")]
+use std::ptr;
+#[cfg(target_os = "macos")]
+use std::sync::Arc;
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CGImageAlphaInfo {
     CGImageAlphaNone, /* For example, RGB. */
     CGImageAlphaPremultipliedLast, /* For example, premultiplied RGBA */
@@ -18,6 +37,7 @@ pub enum CGImageAlphaInfo {
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CGImageByteOrderInfo {
     CGImageByteOrderMask = 0x7000,
     CGImageByteOrder16Little = (1 << 12),
@@ -26,6 +46,217 @@ pub enum CGImageByteOrderInfo {
     CGImageByteOrder32Big = (4 << 12)
 }
 
+/// The layout of the pixels of a bitmap: whether and where it has an alpha channel, the byte
+/// order of its components or pixels, and whether its components are floats.
+///
+/// This is the `CGBitmapInfo` bit field of the framework, restricted to valid values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CGBitmapInfo {
+    bits: u32,
+}
+
+impl CGBitmapInfo {
+    /// Returns the bitmap info with the given parts. `None` is the default byte order, which
+    /// `CGImageByteOrderMask` also stands for since it is not a byte order itself.
+    pub fn new(alpha_info: CGImageAlphaInfo,
+               byte_order: Option<CGImageByteOrderInfo>,
+               float_components: bool)
+               -> CGBitmapInfo {
+        let byte_order_bits = match byte_order {
+            Some(CGImageByteOrderInfo::CGImageByteOrderMask) | None => 0,
+            Some(byte_order) => byte_order as u32,
+        };
+        let float_bits = if float_components { kCGBitmapFloatComponents } else { 0 };
+        CGBitmapInfo {
+            bits: alpha_info as u32 | byte_order_bits | float_bits,
+        }
+    }
+
+    /// Returns the bitmap info for `bits`, as built from the `kCGImageAlpha*`,
+    /// `kCGBitmapByteOrder*` and `kCGBitmapFloatComponents` constants, or `None` if they hold an
+    /// unknown alpha info or byte order, or other bits.
+    pub fn from_bits(bits: u32) -> Option<CGBitmapInfo> {
+        let known = kCGBitmapAlphaInfoMask | kCGBitmapByteOrderMask | kCGBitmapFloatComponents;
+        if bits & !known != 0 || bits & kCGBitmapAlphaInfoMask > 7 ||
+                (bits & kCGBitmapByteOrderMask) >> 12 > 4 {
+            return None;
+        }
+        Some(CGBitmapInfo {
+            bits: bits,
+        })
+    }
+
+    /// Returns the bitmap info for the valid parts of `bits`, clearing other bits as well as
+    /// unknown alpha infos and byte orders. Images of newer systems can have bits this crate
+    /// doesn't know, such as the ones describing packed pixel formats.
+    pub fn from_bits_truncate(bits: u32) -> CGBitmapInfo {
+        let mut bits = bits & (kCGBitmapAlphaInfoMask | kCGBitmapByteOrderMask |
+                               kCGBitmapFloatComponents);
+        if bits & kCGBitmapAlphaInfoMask > 7 {
+            bits &= !kCGBitmapAlphaInfoMask;
+        }
+        if (bits & kCGBitmapByteOrderMask) >> 12 > 4 {
+            bits &= !kCGBitmapByteOrderMask;
+        }
+        CGBitmapInfo {
+            bits: bits,
+        }
+    }
+
+    #[inline]
+    pub fn bits(self) -> u32 {
+        self.bits
+    }
+
+    pub fn alpha_info(self) -> CGImageAlphaInfo {
+        match self.bits & kCGBitmapAlphaInfoMask {
+            0 => CGImageAlphaInfo::CGImageAlphaNone,
+            1 => CGImageAlphaInfo::CGImageAlphaPremultipliedLast,
+            2 => CGImageAlphaInfo::CGImageAlphaPremultipliedFirst,
+            3 => CGImageAlphaInfo::CGImageAlphaLast,
+            4 => CGImageAlphaInfo::CGImageAlphaFirst,
+            5 => CGImageAlphaInfo::CGImageAlphaNoneSkipLast,
+            6 => CGImageAlphaInfo::CGImageAlphaNoneSkipFirst,
+            _ => CGImageAlphaInfo::CGImageAlphaOnly,
+        }
+    }
+
+    /// Returns the byte order, or `None` for the default one.
+    pub fn byte_order(self) -> Option<CGImageByteOrderInfo> {
+        match (self.bits & kCGBitmapByteOrderMask) >> 12 {
+            0 => None,
+            1 => Some(CGImageByteOrderInfo::CGImageByteOrder16Little),
+            2 => Some(CGImageByteOrderInfo::CGImageByteOrder32Little),
+            3 => Some(CGImageByteOrderInfo::CGImageByteOrder16Big),
+            _ => Some(CGImageByteOrderInfo::CGImageByteOrder32Big),
+        }
+    }
+
+    #[inline]
+    pub fn has_float_components(self) -> bool {
+        self.bits & kCGBitmapFloatComponents != 0
+    }
+
+    /// Checks that bitmap contexts support this pixel format with a color space of the given
+    /// model, or no color space for `None`, and returns the number of bits per pixel.
+    ///
+    /// The supported formats are the ones listed in the Quartz 2D Programming Guide. 32-bit
+    /// components must be floats, and other ones integers. The default byte order is always
+    /// allowed, 16-bit byte orders with 16-bit components or pixels, and 32-bit byte orders with
+    /// 32-bit components or pixels.
+    pub fn validate(self,
+                    model: Option<CGColorSpaceModel>,
+                    bits_per_component: usize)
+                    -> Result<usize, BitmapFormatError> {
+        use self::CGImageAlphaInfo::*;
+
+        let alpha = self.alpha_info();
+        let skip_or_premultiplied = |alpha| {
+            matches!(alpha, CGImageAlphaNoneSkipFirst | CGImageAlphaNoneSkipLast |
+                            CGImageAlphaPremultipliedFirst | CGImageAlphaPremultipliedLast)
+        };
+        let bits_per_pixel = match (model, bits_per_component, alpha) {
+            (None, 8, CGImageAlphaOnly) => Some(8),
+            (Some(CGColorSpaceModel::Monochrome), 8, CGImageAlphaNone) |
+            (Some(CGColorSpaceModel::Monochrome), 8, CGImageAlphaOnly) => Some(8),
+            (Some(CGColorSpaceModel::Monochrome), 16, CGImageAlphaNone) => Some(16),
+            (Some(CGColorSpaceModel::Monochrome), 32, CGImageAlphaNone) => Some(32),
+            (Some(CGColorSpaceModel::RGB), 5, CGImageAlphaNoneSkipFirst) => Some(16),
+            (Some(CGColorSpaceModel::RGB), 8, alpha) if skip_or_premultiplied(alpha) => Some(32),
+            (Some(CGColorSpaceModel::RGB), 16, CGImageAlphaPremultipliedLast) |
+            (Some(CGColorSpaceModel::RGB), 16, CGImageAlphaNoneSkipLast) => Some(64),
+            (Some(CGColorSpaceModel::RGB), 32, CGImageAlphaPremultipliedLast) |
+            (Some(CGColorSpaceModel::RGB), 32, CGImageAlphaNoneSkipLast) => Some(128),
+            (Some(CGColorSpaceModel::CMYK), 8, CGImageAlphaNone) => Some(32),
+            (Some(CGColorSpaceModel::CMYK), 16, CGImageAlphaNone) => Some(64),
+            (Some(CGColorSpaceModel::CMYK), 32, CGImageAlphaNone) => Some(128),
+            _ => None,
+        };
+        let byte_order_matches = match self.byte_order() {
+            Some(CGImageByteOrderInfo::CGImageByteOrder16Little) |
+            Some(CGImageByteOrderInfo::CGImageByteOrder16Big) => {
+                bits_per_component == 16 || bits_per_pixel == Some(16)
+            }
+            Some(CGImageByteOrderInfo::CGImageByteOrder32Little) |
+            Some(CGImageByteOrderInfo::CGImageByteOrder32Big) => {
+                bits_per_component == 32 || bits_per_pixel == Some(32)
+            }
+            _ => true,
+        };
+        match bits_per_pixel {
+            Some(bits_per_pixel) if byte_order_matches &&
+                    self.has_float_components() == (bits_per_component == 32) => {
+                Ok(bits_per_pixel)
+            }
+            _ => {
+                Err(BitmapFormatError::UnsupportedFormat {
+                    model: model,
+                    bits_per_component: bits_per_component,
+                    bitmap_info: self,
+                })
+            }
+        }
+    }
+}
+
+impl From<CGBitmapInfo> for u32 {
+    #[inline]
+    fn from(bitmap_info: CGBitmapInfo) -> u32 {
+        bitmap_info.bits
+    }
+}
+
+/// The reason a bitmap can't be created with a given pixel format and size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitmapFormatError {
+    /// The bits don't make up a valid `CGBitmapInfo`.
+    InvalidBitmapInfo(u32),
+    /// Quartz doesn't support this combination of color space, component size and bitmap info.
+    UnsupportedFormat {
+        model: Option<CGColorSpaceModel>,
+        bits_per_component: usize,
+        bitmap_info: CGBitmapInfo,
+    },
+    /// Rows are too short for the width and pixel format.
+    BytesPerRowTooSmall { bytes_per_row: usize, minimum: usize },
+    /// The size of a row or of the whole bitmap doesn't fit in a `usize`.
+    Overflow,
+    /// The framework refused to create the bitmap nevertheless.
+    CreationFailed,
+}
+
+impl Display for BitmapFormatError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BitmapFormatError::InvalidBitmapInfo(bits) => {
+                write!(formatter, "invalid bitmap info {:#x}", bits)
+            }
+            BitmapFormatError::UnsupportedFormat { model, bits_per_component, bitmap_info } => {
+                let byte_order = match bitmap_info.byte_order() {
+                    Some(byte_order) => format!("{:?}", byte_order),
+                    None => "default byte order".to_owned(),
+                };
+                write!(formatter,
+                       "unsupported pixel format: {:?} color space, {} bits per component, {:?}, \
+                        {}{}",
+                       model,
+                       bits_per_component,
+                       bitmap_info.alpha_info(),
+                       byte_order,
+                       if bitmap_info.has_float_components() { ", float components" } else { "" })
+            }
+            BitmapFormatError::BytesPerRowTooSmall { bytes_per_row, minimum } => {
+                write!(formatter, "{} bytes per row instead of at least {}", bytes_per_row, minimum)
+            }
+            BitmapFormatError::Overflow => write!(formatter, "bitmap size overflow"),
+            BitmapFormatError::CreationFailed => write!(formatter, "bitmap creation failed"),
+        }
+    }
+}
+
+impl Error for BitmapFormatError {}
+
+#[cfg(target_os = "macos")]
 foreign_type! {
     #[doc(hidden)]
     type CType = ::sys::CGImage;
@@ -35,6 +266,7 @@ foreign_type! {
     pub struct CGImageRef;
 }
 
+#[cfg(target_os = "macos")]
 impl CGImage {
     pub fn type_id() -> CFTypeID {
         unsafe {
@@ -43,6 +275,7 @@ impl CGImage {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl CGImageRef {
     pub fn width(&self) -> size_t {
         unsafe {
@@ -74,6 +307,55 @@ impl CGImageRef {
         }
     }
 
+    /// Returns the alpha info part of `bitmap_info`: whether the image has an alpha channel,
+    /// whether it comes first or last in each pixel, and whether colors are premultiplied by it.
+    pub fn alpha_info(&self) -> CGImageAlphaInfo {
+        let alpha_info = unsafe {
+            CGImageGetAlphaInfo(self.as_ptr())
+        };
+        CGBitmapInfo::from_bits_truncate(alpha_info & kCGBitmapAlphaInfoMask).alpha_info()
+    }
+
+    /// Returns the layout of the pixels returned by `data`.
+    ///
+    /// With the default byte order, components are stored in the order given by the alpha info
+    /// and the color space, e.g. RGBA for `CGImageAlphaPremultipliedLast` in an RGB color space.
+    /// With a 32-bit little-endian byte order, the bytes of each pixel are reversed, e.g. BGRA
+    /// for `CGImageAlphaPremultipliedFirst`, which is what screenshots usually use.
+    pub fn bitmap_info(&self) -> CGBitmapInfo {
+        unsafe {
+            CGBitmapInfo::from_bits_truncate(CGImageGetBitmapInfo(self.as_ptr()))
+        }
+    }
+
+    /// Returns the rendering intent used to match the colors of the image with the destination.
+    pub fn rendering_intent(&self) -> CGColorRenderingIntent {
+        let intent = unsafe {
+            CGImageGetRenderingIntent(self.as_ptr())
+        };
+        match intent {
+            1 => CGColorRenderingIntent::AbsoluteColorimetric,
+            2 => CGColorRenderingIntent::RelativeColorimetric,
+            3 => CGColorRenderingIntent::Perceptual,
+            4 => CGColorRenderingIntent::Saturation,
+            _ => CGColorRenderingIntent::Default,
+        }
+    }
+
+    /// Returns true if the image is smoothed when drawn scaled up.
+    pub fn should_interpolate(&self) -> bool {
+        unsafe {
+            CGImageGetShouldInterpolate(self.as_ptr())
+        }
+    }
+
+    /// Returns true if the image is a mask, made of coverage values rather than colors.
+    pub fn is_mask(&self) -> bool {
+        unsafe {
+            CGImageIsMask(self.as_ptr())
+        }
+    }
+
     pub fn color_space(&self) -> CGColorSpace {
         unsafe {
             let cs = CGImageGetColorSpace(self.as_ptr());
@@ -90,11 +372,279 @@ impl CGImageRef {
         };
         data_provider.copy_data()
     }
+
+    /// Returns a view of the rows and pixels of `data`, which should be the bytes returned by
+    /// `data`, using the size and layout of this image.
+    ///
+    /// ```ignore
+    /// let data = image.data();
+    /// let view = image.pixel_view(data.bytes())?;
+    /// let top_left = view.pixel(0, 0);
+    /// ```
+    pub fn pixel_view<'a>(&self, data: &'a [u8]) -> Result<PixelView<'a>, PixelError> {
+        PixelView::new(data,
+                       self.width(),
+                       self.height(),
+                       self.bytes_per_row(),
+                       self.bits_per_pixel())
+    }
+}
+
+/// Creates images from pixel data with `CGImageCreate`.
+///
+/// The pixel format is given by the number of bits per component, the bitmap info and the
+/// number of components of the color space. Pixels hold the color components, plus one for
+/// alpha or a skipped byte unless the alpha info is `CGImageAlphaNone`. Rows are `bytes_per_row`
+/// bytes long, which defaults to the length of a row without padding.
+///
+/// Images support more formats than bitmap contexts, such as non-premultiplied alpha, RGB
+/// without alpha and indexed colors, so they are not checked with `CGBitmapInfo::validate`.
+///
+/// All sizes are checked before calling the framework, so that an invalid format or a buffer
+/// too short for the image gives an error.
+#[cfg(target_os = "macos")]
+#[derive(Clone)]
+pub struct CGImageBuilder {
+    format: ImageFormat,
+    color_space: CGColorSpace,
+    decode: Option<Vec<CGFloat>>,
+    should_interpolate: bool,
+    rendering_intent: CGColorRenderingIntent,
 }
 
+#[cfg(target_os = "macos")]
+impl CGImageBuilder {
+    pub fn new(width: usize,
+               height: usize,
+               bits_per_component: usize,
+               bitmap_info: CGBitmapInfo,
+               color_space: &CGColorSpace)
+               -> CGImageBuilder {
+        CGImageBuilder {
+            format: ImageFormat {
+                width: width,
+                height: height,
+                bits_per_component: bits_per_component,
+                bitmap_info: bitmap_info,
+                bytes_per_row: None,
+            },
+            color_space: color_space.clone(),
+            decode: None,
+            should_interpolate: false,
+            rendering_intent: CGColorRenderingIntent::Default,
+        }
+    }
+
+    /// Sets the distance between the starts of consecutive rows, in bytes.
+    pub fn bytes_per_row(mut self, bytes_per_row: usize) -> CGImageBuilder {
+        self.format.bytes_per_row = Some(bytes_per_row);
+        self
+    }
+
+    /// Sets the decode array, which maps each color component to a range: a pair of values
+    /// per color component, not counting alpha.
+    pub fn decode(mut self, decode: &[CGFloat]) -> CGImageBuilder {
+        self.decode = Some(decode.to_vec());
+        self
+    }
+
+    /// Sets whether the image is smoothed when drawn scaled up. Defaults to false.
+    pub fn should_interpolate(mut self, should_interpolate: bool) -> CGImageBuilder {
+        self.should_interpolate = should_interpolate;
+        self
+    }
+
+    /// Defaults to `CGColorRenderingIntent::Default`.
+    pub fn rendering_intent(mut self, rendering_intent: CGColorRenderingIntent)
+                            -> CGImageBuilder {
+        self.rendering_intent = rendering_intent;
+        self
+    }
+
+    /// Creates an image reading its pixels from `provider`.
+    ///
+    /// The data of the provider is copied once to check its length, so `build_from_buffer` is
+    /// cheaper when the pixels are in memory.
+    pub fn build(&self, provider: &CGDataProviderRef) -> Result<CGImage, ImageCreationError> {
+        let layout = self.layout(provider.copy_data().len() as usize)?;
+        self.create(layout, provider)
+    }
+
+    /// Creates an image whose pixels are in `buffer`, which it keeps alive.
+    pub fn build_from_buffer(&self, buffer: Arc<Vec<u8>>)
+                             -> Result<CGImage, ImageCreationError> {
+        let layout = self.layout(buffer.len())?;
+        self.create(layout, &CGDataProvider::from_buffer(buffer))
+    }
+
+    fn layout(&self, length: usize) -> Result<ImageLayout, ImageCreationError> {
+        let color_components = self.color_space.number_of_components();
+        let layout = self.format.layout(self.color_space.model(), color_components, length)?;
+        if let Some(ref decode) = self.decode {
+            if decode.len() != color_components * 2 {
+                return Err(ImageCreationError::DecodeLength {
+                    expected: color_components * 2,
+                    actual: decode.len(),
+                });
+            }
+        }
+        Ok(layout)
+    }
+
+    fn create(&self, layout: ImageLayout, provider: &CGDataProviderRef)
+              -> Result<CGImage, ImageCreationError> {
+        let decode = self.decode.as_ref().map_or(ptr::null(), |decode| decode.as_ptr());
+        unsafe {
+            let result = CGImageCreate(self.format.width,
+                                       self.format.height,
+                                       self.format.bits_per_component,
+                                       layout.bits_per_pixel,
+                                       layout.bytes_per_row,
+                                       self.color_space.as_ptr(),
+                                       self.format.bitmap_info.bits(),
+                                       provider.as_ptr(),
+                                       decode,
+                                       self.should_interpolate,
+                                       self.rendering_intent as i32);
+            if result.is_null() {
+                Err(ImageCreationError::Bitmap(BitmapFormatError::CreationFailed))
+            } else {
+                Ok(CGImage::from_ptr(result))
+            }
+        }
+    }
+}
+
+/// The dimensions and pixel format of a `CGImageBuilder`, which don't depend on the framework.
+#[cfg(any(test, target_os = "macos"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ImageFormat {
+    width: usize,
+    height: usize,
+    bits_per_component: usize,
+    bitmap_info: CGBitmapInfo,
+    bytes_per_row: Option<usize>,
+}
+
+#[cfg(any(test, target_os = "macos"))]
+impl ImageFormat {
+    /// Checks the format and sizes of the image for a color space of the given model with
+    /// `color_components` components, and `length` bytes of data.
+    fn layout(&self, model: CGColorSpaceModel, color_components: usize, length: usize)
+              -> Result<ImageLayout, ImageCreationError> {
+        use self::CGImageAlphaInfo::*;
+
+        let unsupported = BitmapFormatError::UnsupportedFormat {
+            model: Some(model),
+            bits_per_component: self.bits_per_component,
+            bitmap_info: self.bitmap_info,
+        };
+        if self.width == 0 || self.height == 0 {
+            return Err(ImageCreationError::Empty);
+        }
+        let components = match self.bitmap_info.alpha_info() {
+            _ if color_components == 0 => return Err(unsupported.into()),
+            CGImageAlphaNone => color_components,
+            CGImageAlphaOnly => return Err(unsupported.into()),
+            _ => color_components + 1,
+        };
+        let float_components = self.bitmap_info.has_float_components();
+        let bits_per_pixel = match (self.bits_per_component, self.bitmap_info.alpha_info()) {
+            (1, _) | (2, _) | (4, _) | (8, _) if !float_components => {
+                components * self.bits_per_component
+            }
+            (16, _) | (32, _) => components * self.bits_per_component,
+            // The 5-bit formats leave one bit unused.
+            (5, CGImageAlphaNoneSkipFirst) | (5, CGImageAlphaNoneSkipLast)
+                    if components == 4 && !float_components => 16,
+            _ => return Err(unsupported.into()),
+        };
+
+        let minimum = self.width.checked_mul(bits_per_pixel)
+                                .and_then(|bits| bits.checked_add(7))
+                                .ok_or(BitmapFormatError::Overflow)? / 8;
+        let bytes_per_row = self.bytes_per_row.unwrap_or(minimum);
+        if bytes_per_row < minimum {
+            return Err(BitmapFormatError::BytesPerRowTooSmall {
+                bytes_per_row: bytes_per_row,
+                minimum: minimum,
+            }.into());
+        }
+        let needed = self.height.checked_mul(bytes_per_row).ok_or(BitmapFormatError::Overflow)?;
+        if length < needed {
+            return Err(ImageCreationError::BufferTooShort {
+                length: length,
+                needed: needed,
+            });
+        }
+        Ok(ImageLayout {
+            bits_per_pixel: bits_per_pixel,
+            bytes_per_row: bytes_per_row,
+        })
+    }
+}
+
+#[cfg(any(test, target_os = "macos"))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ImageLayout {
+    bits_per_pixel: usize,
+    bytes_per_row: usize,
+}
+
+/// The reason `CGImageBuilder` could not create an image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageCreationError {
+    /// The width or height is zero.
+    Empty,
+    /// The pixel format or row length is invalid, the size of the image doesn't fit in a
+    /// `usize`, or the framework refused to create the image nevertheless.
+    Bitmap(BitmapFormatError),
+    /// The decode array doesn't have two values per color component.
+    DecodeLength { expected: usize, actual: usize },
+    /// The data is shorter than `height * bytes_per_row`.
+    BufferTooShort { length: usize, needed: usize },
+}
+
+impl From<BitmapFormatError> for ImageCreationError {
+    #[inline]
+    fn from(error: BitmapFormatError) -> ImageCreationError {
+        ImageCreationError::Bitmap(error)
+    }
+}
+
+impl Display for ImageCreationError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ImageCreationError::Empty => write!(formatter, "image has no pixels"),
+            ImageCreationError::Bitmap(ref error) => error.fmt(formatter),
+            ImageCreationError::DecodeLength { expected, actual } => {
+                write!(formatter, "decode array has {} values instead of {}", actual, expected)
+            }
+            ImageCreationError::BufferTooShort { length, needed } => {
+                write!(formatter, "image needs {} bytes of data but has {}", needed, length)
+            }
+        }
+    }
+}
+
+impl Error for ImageCreationError {}
+
+#[cfg(target_os = "macos")]
 #[link(name = "CoreGraphics", kind = "framework")]
 extern {
     fn CGImageGetTypeID() -> CFTypeID;
+    fn CGImageCreate(width: size_t,
+                     height: size_t,
+                     bitsPerComponent: size_t,
+                     bitsPerPixel: size_t,
+                     bytesPerRow: size_t,
+                     space: ::sys::CGColorSpaceRef,
+                     bitmapInfo: u32,
+                     provider: ::sys::CGDataProviderRef,
+                     decode: *const CGFloat,
+                     shouldInterpolate: bool,
+                     intent: i32)
+                     -> ::sys::CGImageRef;
     fn CGImageGetWidth(image: ::sys::CGImageRef) -> size_t;
     fn CGImageGetHeight(image: ::sys::CGImageRef) -> size_t;
     fn CGImageGetBitsPerComponent(image: ::sys::CGImageRef) -> size_t;
@@ -102,8 +652,164 @@ extern {
     fn CGImageGetBytesPerRow(image: ::sys::CGImageRef) -> size_t;
     fn CGImageGetColorSpace(image: ::sys::CGImageRef) -> ::sys::CGColorSpaceRef;
     fn CGImageGetDataProvider(image: ::sys::CGImageRef) -> ::sys::CGDataProviderRef;
+    fn CGImageGetAlphaInfo(image: ::sys::CGImageRef) -> u32;
+    fn CGImageGetBitmapInfo(image: ::sys::CGImageRef) -> u32;
+    fn CGImageGetRenderingIntent(image: ::sys::CGImageRef) -> i32;
+    fn CGImageGetShouldInterpolate(image: ::sys::CGImageRef) -> bool;
+    fn CGImageIsMask(image: ::sys::CGImageRef) -> bool;
     fn CGImageRelease(image: ::sys::CGImageRef);
 
-    //fn CGImageGetAlphaInfo(image: ::sys::CGImageRef) -> CGImageAlphaInfo;
     //fn CGImageCreateCopyWithColorSpace(image: ::sys::CGImageRef, space: ::sys::CGColorSpaceRef) -> ::sys::CGImageRef
 }
+
+#[test]
+fn bitmap_info_validation() {
+    use base::*;
+
+    let rgba = CGBitmapInfo::from_bits(kCGImageAlphaPremultipliedLast).unwrap();
+    assert_eq!(rgba.alpha_info(), CGImageAlphaInfo::CGImageAlphaPremultipliedLast);
+    assert_eq!(rgba.byte_order(), None);
+    assert!(!rgba.has_float_components());
+    let bgra = CGBitmapInfo::new(CGImageAlphaInfo::CGImageAlphaPremultipliedFirst,
+                                 Some(CGImageByteOrderInfo::CGImageByteOrder32Little),
+                                 false);
+    assert_eq!(bgra.bits(), kCGImageAlphaPremultipliedFirst | kCGBitmapByteOrder32Little);
+    assert_eq!(bgra.byte_order(), Some(CGImageByteOrderInfo::CGImageByteOrder32Little));
+    let mask = CGBitmapInfo::new(CGImageAlphaInfo::CGImageAlphaPremultipliedLast,
+                                 Some(CGImageByteOrderInfo::CGImageByteOrderMask),
+                                 false);
+    assert_eq!(mask, rgba);
+    assert_eq!(CGBitmapInfo::from_bits(u32::from(bgra)), Some(bgra));
+    assert_eq!(CGBitmapInfo::from_bits(8), None);
+    assert_eq!(CGBitmapInfo::from_bits(5 << 12), None);
+    assert_eq!(CGBitmapInfo::from_bits(1 << 16), None);
+    assert_eq!(CGBitmapInfo::from_bits_truncate(bgra.bits() | 3 << 16), bgra);
+    assert_eq!(CGBitmapInfo::from_bits_truncate(8 | kCGBitmapByteOrder16Big).bits(),
+               kCGBitmapByteOrder16Big);
+    assert_eq!(CGBitmapInfo::from_bits_truncate(kCGImageAlphaLast | 7 << 12).bits(),
+               kCGImageAlphaLast);
+
+    let rgb = Some(CGColorSpaceModel::RGB);
+    assert_eq!(rgba.validate(rgb, 8), Ok(32));
+    assert_eq!(bgra.validate(rgb, 8), Ok(32));
+    assert_eq!(rgba.validate(rgb, 16), Ok(64));
+    let float = CGBitmapInfo::from_bits(kCGImageAlphaPremultipliedLast | kCGBitmapFloatComponents);
+    assert_eq!(float.unwrap().validate(rgb, 32), Ok(128));
+    let skip_first = CGBitmapInfo::from_bits(kCGImageAlphaNoneSkipFirst).unwrap();
+    assert_eq!(skip_first.validate(rgb, 5), Ok(16));
+    let big_16 = CGBitmapInfo::from_bits(kCGImageAlphaNoneSkipFirst | kCGBitmapByteOrder16Big);
+    assert_eq!(big_16.unwrap().validate(rgb, 5), Ok(16));
+    let no_alpha = CGBitmapInfo::from_bits(kCGImageAlphaNone).unwrap();
+    assert_eq!(no_alpha.validate(Some(CGColorSpaceModel::Monochrome), 16), Ok(16));
+    assert_eq!(no_alpha.validate(Some(CGColorSpaceModel::CMYK), 8), Ok(32));
+    let alpha_only = CGBitmapInfo::from_bits(kCGImageAlphaOnly).unwrap();
+    assert_eq!(alpha_only.validate(None, 8), Ok(8));
+
+    let unsupported = |info: CGBitmapInfo, model, bits_per_component| {
+        assert_eq!(info.validate(model, bits_per_component),
+                   Err(BitmapFormatError::UnsupportedFormat {
+                       model: model,
+                       bits_per_component: bits_per_component,
+                       bitmap_info: info,
+                   }));
+    };
+    // RGB without alpha or skipped byte, non-premultiplied alpha, and integer 32-bit components.
+    unsupported(no_alpha, rgb, 8);
+    unsupported(CGBitmapInfo::from_bits(kCGImageAlphaLast).unwrap(), rgb, 8);
+    unsupported(rgba, rgb, 32);
+    unsupported(float.unwrap(), rgb, 8);
+    unsupported(bgra, rgb, 16);
+    unsupported(alpha_only, rgb, 8);
+    unsupported(no_alpha, None, 8);
+    unsupported(rgba, Some(CGColorSpaceModel::Indexed), 8);
+}
+
+#[test]
+fn image_builder_layout() {
+    use base::*;
+
+    let info = |bits| CGBitmapInfo::from_bits(bits).unwrap();
+    let format = |width, height, bits_per_component, bitmap_info| {
+        ImageFormat {
+            width: width,
+            height: height,
+            bits_per_component: bits_per_component,
+            bitmap_info: bitmap_info,
+            bytes_per_row: None,
+        }
+    };
+    let rgb = CGColorSpaceModel::RGB;
+    let layout = |format: &ImageFormat, length| format.layout(rgb, 3, length);
+    let rgba = format(10, 4, 8, info(kCGImageAlphaPremultipliedLast));
+    assert_eq!(layout(&rgba, 160), Ok(ImageLayout { bits_per_pixel: 32, bytes_per_row: 40 }));
+    assert_eq!(layout(&rgba, 159), Err(ImageCreationError::BufferTooShort {
+        length: 159,
+        needed: 160,
+    }));
+    let padded = ImageFormat { bytes_per_row: Some(48), ..rgba };
+    assert_eq!(layout(&padded, 192), Ok(ImageLayout { bits_per_pixel: 32, bytes_per_row: 48 }));
+    assert_eq!(layout(&padded, 180), Err(ImageCreationError::BufferTooShort {
+        length: 180,
+        needed: 192,
+    }));
+    assert_eq!(layout(&ImageFormat { bytes_per_row: Some(39), ..rgba }, 1000),
+               Err(ImageCreationError::Bitmap(BitmapFormatError::BytesPerRowTooSmall {
+                   bytes_per_row: 39,
+                   minimum: 40,
+               })));
+
+    // Formats bitmap contexts don't support: straight alpha, no alpha, sub-byte and indexed.
+    let straight = format(2, 1, 8, info(kCGImageAlphaLast));
+    assert_eq!(layout(&straight, 8), Ok(ImageLayout { bits_per_pixel: 32, bytes_per_row: 8 }));
+    let rgb_24 = format(5, 1, 8, info(kCGImageAlphaNone));
+    assert_eq!(layout(&rgb_24, 15), Ok(ImageLayout { bits_per_pixel: 24, bytes_per_row: 15 }));
+    let gray = format(9, 2, 1, info(kCGImageAlphaNone));
+    assert_eq!(gray.layout(CGColorSpaceModel::Monochrome, 1, 4),
+               Ok(ImageLayout { bits_per_pixel: 1, bytes_per_row: 2 }));
+    let indexed = format(3, 1, 4, info(kCGImageAlphaNone));
+    assert_eq!(indexed.layout(CGColorSpaceModel::Indexed, 1, 2),
+               Ok(ImageLayout { bits_per_pixel: 4, bytes_per_row: 2 }));
+
+    // Packed, byte-swapped and float formats.
+    let rgb_555 = format(3, 1, 5, info(kCGImageAlphaNoneSkipFirst));
+    assert_eq!(layout(&rgb_555, 6), Ok(ImageLayout { bits_per_pixel: 16, bytes_per_row: 6 }));
+    let bgra = format(2, 1, 8, info(kCGImageAlphaPremultipliedFirst | kCGBitmapByteOrder32Little));
+    assert_eq!(layout(&bgra, 8), Ok(ImageLayout { bits_per_pixel: 32, bytes_per_row: 8 }));
+    let float = info(kCGImageAlphaPremultipliedLast | kCGBitmapFloatComponents);
+    assert_eq!(layout(&format(2, 2, 32, float), 64),
+               Ok(ImageLayout { bits_per_pixel: 128, bytes_per_row: 32 }));
+
+    let unsupported = |format: ImageFormat| {
+        assert_eq!(layout(&format, 1 << 20),
+                   Err(ImageCreationError::Bitmap(BitmapFormatError::UnsupportedFormat {
+                       model: Some(rgb),
+                       bits_per_component: format.bits_per_component,
+                       bitmap_info: format.bitmap_info,
+                   })));
+    };
+    unsupported(format(2, 2, 8, float));
+    unsupported(format(2, 2, 5, info(kCGImageAlphaNone)));
+    unsupported(format(2, 2, 7, info(kCGImageAlphaNone)));
+    unsupported(format(2, 2, 8, info(kCGImageAlphaOnly)));
+    assert!(format(2, 2, 8, info(kCGImageAlphaNone)).layout(CGColorSpaceModel::Pattern, 0, 16)
+                                                    .is_err());
+
+    assert_eq!(layout(&format(0, 4, 8, info(kCGImageAlphaNone)), 0),
+               Err(ImageCreationError::Empty));
+    let overflow = Err(ImageCreationError::Bitmap(BitmapFormatError::Overflow));
+    assert_eq!(layout(&format(usize::MAX / 8, 1, 8, info(kCGImageAlphaNone)), 0), overflow);
+    assert_eq!(layout(&format(1, usize::MAX, 8, info(kCGImageAlphaNone)), 0), overflow);
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn image_builder_decode_length() {
+    use base::kCGImageAlphaPremultipliedLast;
+
+    let space = CGColorSpace::create_device_rgb();
+    let info = CGBitmapInfo::from_bits(kCGImageAlphaPremultipliedLast).unwrap();
+    let rgba = CGImageBuilder::new(10, 4, 8, info, &space);
+    assert_eq!(rgba.clone().decode(&[0.0, 1.0]).layout(160).err(),
+               Some(ImageCreationError::DecodeLength { expected: 6, actual: 2 }));
+    assert!(rgba.decode(&[1.0, 0.0, 1.0, 0.0, 1.0, 0.0]).layout(160).is_ok());
+}
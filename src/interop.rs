@@ -0,0 +1,401 @@
+// Copyright 2018 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversions between the geometry types and those of `euclid` and `mint`, and between paths
+//! and those of `lyon_path`.
+//!
+//! These are enabled by the `euclid`, `mint` and `lyon_path` cargo features respectively.
+//!
+//! Both `CGAffineTransform` and euclid's `Transform2D` use the row-vector convention, so
+//! `a, b, c, d, tx, ty` map to `m11, m12, m21, m22, m31, m32` in that order. In mint the same
+//! matrix is a `ColumnMatrix2x3` whose columns are `(a, b)`, `(c, d)` and `(tx, ty)`.
+
+#[cfg(feature = "euclid")]
+mod euclid_conversions {
+    use base::CGFloat;
+    use euclid::{Point2D, Rect, Size2D, Transform2D};
+    use euclid::{TypedPoint2D, TypedRect as EuclidTypedRect, TypedSize2D, TypedTransform2D};
+    use geometry::{CGAffineTransform, CGPoint, CGRect, CGSize};
+    use typed_geometry::{TypedPoint, TypedRect, TypedSize, TypedTransform};
+
+    impl From<CGPoint> for Point2D<CGFloat> {
+        #[inline]
+        fn from(point: CGPoint) -> Point2D<CGFloat> {
+            Point2D::new(point.x, point.y)
+        }
+    }
+
+    impl From<Point2D<CGFloat>> for CGPoint {
+        #[inline]
+        fn from(point: Point2D<CGFloat>) -> CGPoint {
+            CGPoint::new(point.x, point.y)
+        }
+    }
+
+    impl From<CGSize> for Size2D<CGFloat> {
+        #[inline]
+        fn from(size: CGSize) -> Size2D<CGFloat> {
+            Size2D::new(size.width, size.height)
+        }
+    }
+
+    impl From<Size2D<CGFloat>> for CGSize {
+        #[inline]
+        fn from(size: Size2D<CGFloat>) -> CGSize {
+            CGSize::new(size.width, size.height)
+        }
+    }
+
+    impl From<CGRect> for Rect<CGFloat> {
+        #[inline]
+        fn from(rect: CGRect) -> Rect<CGFloat> {
+            Rect::new(rect.origin.into(), rect.size.into())
+        }
+    }
+
+    impl From<Rect<CGFloat>> for CGRect {
+        #[inline]
+        fn from(rect: Rect<CGFloat>) -> CGRect {
+            CGRect::new(&rect.origin.into(), &rect.size.into())
+        }
+    }
+
+    impl From<CGAffineTransform> for Transform2D<CGFloat> {
+        #[inline]
+        fn from(t: CGAffineTransform) -> Transform2D<CGFloat> {
+            Transform2D::row_major(t.a, t.b, t.c, t.d, t.tx, t.ty)
+        }
+    }
+
+    impl From<Transform2D<CGFloat>> for CGAffineTransform {
+        #[inline]
+        fn from(t: Transform2D<CGFloat>) -> CGAffineTransform {
+            CGAffineTransform::new(t.m11, t.m12, t.m21, t.m22, t.m31, t.m32)
+        }
+    }
+
+    impl<Space> From<TypedPoint<Space>> for TypedPoint2D<CGFloat, Space> {
+        #[inline]
+        fn from(point: TypedPoint<Space>) -> TypedPoint2D<CGFloat, Space> {
+            TypedPoint2D::new(point.to_untyped().x, point.to_untyped().y)
+        }
+    }
+
+    impl<Space> From<TypedPoint2D<CGFloat, Space>> for TypedPoint<Space> {
+        #[inline]
+        fn from(point: TypedPoint2D<CGFloat, Space>) -> TypedPoint<Space> {
+            TypedPoint::new(point.x, point.y)
+        }
+    }
+
+    impl<Space> From<TypedSize<Space>> for TypedSize2D<CGFloat, Space> {
+        #[inline]
+        fn from(size: TypedSize<Space>) -> TypedSize2D<CGFloat, Space> {
+            TypedSize2D::new(size.to_untyped().width, size.to_untyped().height)
+        }
+    }
+
+    impl<Space> From<TypedSize2D<CGFloat, Space>> for TypedSize<Space> {
+        #[inline]
+        fn from(size: TypedSize2D<CGFloat, Space>) -> TypedSize<Space> {
+            TypedSize::new(size.width, size.height)
+        }
+    }
+
+    impl<Space> From<TypedRect<Space>> for EuclidTypedRect<CGFloat, Space> {
+        #[inline]
+        fn from(rect: TypedRect<Space>) -> EuclidTypedRect<CGFloat, Space> {
+            EuclidTypedRect::new(rect.origin().into(), rect.size().into())
+        }
+    }
+
+    impl<Space> From<EuclidTypedRect<CGFloat, Space>> for TypedRect<Space> {
+        #[inline]
+        fn from(rect: EuclidTypedRect<CGFloat, Space>) -> TypedRect<Space> {
+            TypedRect::new(&rect.origin.into(), &rect.size.into())
+        }
+    }
+
+    impl<Src, Dst> From<TypedTransform<Src, Dst>> for TypedTransform2D<CGFloat, Src, Dst> {
+        #[inline]
+        fn from(t: TypedTransform<Src, Dst>) -> TypedTransform2D<CGFloat, Src, Dst> {
+            let t = t.to_untyped();
+            TypedTransform2D::row_major(t.a, t.b, t.c, t.d, t.tx, t.ty)
+        }
+    }
+
+    impl<Src, Dst> From<TypedTransform2D<CGFloat, Src, Dst>> for TypedTransform<Src, Dst> {
+        #[inline]
+        fn from(t: TypedTransform2D<CGFloat, Src, Dst>) -> TypedTransform<Src, Dst> {
+            TypedTransform::from_untyped(&CGAffineTransform::from(t.to_untyped()))
+        }
+    }
+
+    #[test]
+    fn euclid_round_trip() {
+        let rect = CGRect::new(&CGPoint::new(1.0, 2.0), &CGSize::new(3.0, 4.0));
+        let euclid_rect: Rect<CGFloat> = rect.into();
+        assert_eq!(euclid_rect, Rect::new(Point2D::new(1.0, 2.0), Size2D::new(3.0, 4.0)));
+        let back: CGRect = euclid_rect.into();
+        assert_eq!((back.origin.x, back.origin.y, back.size.width, back.size.height),
+                   (1.0, 2.0, 3.0, 4.0));
+
+        let t = CGAffineTransform::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+        let euclid_t: Transform2D<CGFloat> = t.into();
+        assert_eq!(euclid_t.to_row_major_array(), [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let back: CGAffineTransform = euclid_t.into();
+        assert_eq!((back.a, back.b, back.c, back.d, back.tx, back.ty),
+                   (1.0, 2.0, 3.0, 4.0, 5.0, 6.0));
+
+        // Both libraries must agree on where a point ends up.
+        let point = CGPoint::new(-7.0, 0.5);
+        let expected = point.apply_transform(&t);
+        let actual = euclid_t.transform_point(&point.into());
+        assert_eq!((actual.x, actual.y), (expected.x, expected.y));
+    }
+}
+
+#[cfg(feature = "mint")]
+mod mint_conversions {
+    use base::CGFloat;
+    use geometry::{CGAffineTransform, CGPoint, CGSize};
+    use mint::{ColumnMatrix2x3, Point2, Vector2};
+
+    impl From<CGPoint> for Point2<CGFloat> {
+        #[inline]
+        fn from(point: CGPoint) -> Point2<CGFloat> {
+            Point2 { x: point.x, y: point.y }
+        }
+    }
+
+    impl From<Point2<CGFloat>> for CGPoint {
+        #[inline]
+        fn from(point: Point2<CGFloat>) -> CGPoint {
+            CGPoint::new(point.x, point.y)
+        }
+    }
+
+    impl From<CGSize> for Vector2<CGFloat> {
+        #[inline]
+        fn from(size: CGSize) -> Vector2<CGFloat> {
+            Vector2 { x: size.width, y: size.height }
+        }
+    }
+
+    impl From<Vector2<CGFloat>> for CGSize {
+        #[inline]
+        fn from(size: Vector2<CGFloat>) -> CGSize {
+            CGSize::new(size.x, size.y)
+        }
+    }
+
+    impl From<CGAffineTransform> for ColumnMatrix2x3<CGFloat> {
+        #[inline]
+        fn from(t: CGAffineTransform) -> ColumnMatrix2x3<CGFloat> {
+            ColumnMatrix2x3 {
+                x: Vector2 { x: t.a, y: t.b },
+                y: Vector2 { x: t.c, y: t.d },
+                z: Vector2 { x: t.tx, y: t.ty },
+            }
+        }
+    }
+
+    impl From<ColumnMatrix2x3<CGFloat>> for CGAffineTransform {
+        #[inline]
+        fn from(m: ColumnMatrix2x3<CGFloat>) -> CGAffineTransform {
+            CGAffineTransform::new(m.x.x, m.x.y, m.y.x, m.y.y, m.z.x, m.z.y)
+        }
+    }
+
+    #[test]
+    fn mint_round_trip() {
+        let point: Point2<CGFloat> = CGPoint::new(1.0, 2.0).into();
+        assert_eq!(point, Point2 { x: 1.0, y: 2.0 });
+        let size: CGSize = Vector2 { x: 3.0, y: 4.0 }.into();
+        assert_eq!((size.width, size.height), (3.0, 4.0));
+
+        let t = CGAffineTransform::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+        let m: ColumnMatrix2x3<CGFloat> = t.into();
+        // The first row of the matrix is `a c tx`.
+        assert_eq!((m.x.x, m.y.x, m.z.x), (1.0, 3.0, 5.0));
+        let back: CGAffineTransform = m.into();
+        assert_eq!((back.a, back.b, back.c, back.d, back.tx, back.ty),
+                   (1.0, 2.0, 3.0, 4.0, 5.0, 6.0));
+    }
+}
+
+#[cfg(feature = "lyon_path")]
+mod lyon_conversions {
+    use base::CGFloat;
+    use geometry::CGPoint;
+    use lyon_path::math::{point, Point};
+    use lyon_path::{Path, PathEvent};
+    use path::{CGMutablePath, CGPath, CGPathRef, PathElement};
+
+    // lyon works in `f32`, so coordinates are rounded on the way there.
+    #[inline]
+    fn to_lyon(p: CGPoint) -> Point {
+        point(p.x as f32, p.y as f32)
+    }
+
+    #[inline]
+    fn from_lyon(p: Point) -> CGPoint {
+        CGPoint::new(p.x as CGFloat, p.y as CGFloat)
+    }
+
+    impl CGPathRef {
+        /// Returns the path as lyon events.
+        ///
+        /// Every subpath is wrapped in `Begin` and `End` events as lyon requires. `CloseSubpath`
+        /// becomes `End { close: true }` and any other subpath ends with `close: false`. A segment
+        /// following a close, without a move first, begins a new subpath at the start of the
+        /// closed one.
+        pub fn lyon_events(&self) -> Vec<PathEvent> {
+            let mut events = vec![];
+            // The start of the current subpath, and the end point so far if it is still open.
+            let mut first = point(0.0, 0.0);
+            let mut open: Option<Point> = None;
+            for element in self.elements() {
+                let last = match (element, open) {
+                    (PathElement::MoveTo(p), _) => {
+                        if let Some(last) = open {
+                            events.push(PathEvent::End { last: last, first: first, close: false });
+                        }
+                        first = to_lyon(p);
+                        events.push(PathEvent::Begin { at: first });
+                        open = Some(first);
+                        continue;
+                    }
+                    (PathElement::Close, Some(last)) => {
+                        events.push(PathEvent::End { last: last, first: first, close: true });
+                        open = None;
+                        continue;
+                    }
+                    (PathElement::Close, None) => continue,
+                    (_, Some(last)) => last,
+                    (_, None) => {
+                        events.push(PathEvent::Begin { at: first });
+                        first
+                    }
+                };
+                let (event, to) = match element {
+                    PathElement::LineTo(p) => {
+                        let to = to_lyon(p);
+                        (PathEvent::Line { from: last, to: to }, to)
+                    }
+                    PathElement::QuadTo(c, p) => {
+                        let to = to_lyon(p);
+                        (PathEvent::Quadratic { from: last, ctrl: to_lyon(c), to: to }, to)
+                    }
+                    PathElement::CubicTo(c1, c2, p) => {
+                        let to = to_lyon(p);
+                        (PathEvent::Cubic {
+                            from: last,
+                            ctrl1: to_lyon(c1),
+                            ctrl2: to_lyon(c2),
+                            to: to,
+                        }, to)
+                    }
+                    PathElement::MoveTo(_) | PathElement::Close => unreachable!(),
+                };
+                events.push(event);
+                open = Some(to);
+            }
+            if let Some(last) = open {
+                events.push(PathEvent::End { last: last, first: first, close: false });
+            }
+            events
+        }
+
+        #[inline]
+        pub fn to_lyon_path(&self) -> Path {
+            self.lyon_events().into_iter().collect()
+        }
+    }
+
+    impl CGPath {
+        /// Builds a path from lyon events. `End { close: true }` becomes `CloseSubpath`, and the
+        /// `from` points of segments are ignored in favor of the current point.
+        pub fn from_lyon_events<I>(events: I) -> CGPath where I: IntoIterator<Item = PathEvent> {
+            let mut path = CGMutablePath::new();
+            for event in events {
+                match event {
+                    PathEvent::Begin { at } => path.move_to(from_lyon(at)),
+                    PathEvent::Line { to, .. } => path.line_to(from_lyon(to)),
+                    PathEvent::Quadratic { ctrl, to, .. } => {
+                        path.quad_to(from_lyon(ctrl), from_lyon(to))
+                    }
+                    PathEvent::Cubic { ctrl1, ctrl2, to, .. } => {
+                        path.curve_to(from_lyon(ctrl1), from_lyon(ctrl2), from_lyon(to))
+                    }
+                    PathEvent::End { close: true, .. } => path.close(),
+                    PathEvent::End { close: false, .. } => {}
+                }
+            }
+            path.into_path()
+        }
+    }
+
+    impl<'a> From<&'a CGPathRef> for Path {
+        #[inline]
+        fn from(path: &'a CGPathRef) -> Path {
+            path.to_lyon_path()
+        }
+    }
+
+    impl<'a> From<&'a Path> for CGPath {
+        #[inline]
+        fn from(path: &'a Path) -> CGPath {
+            CGPath::from_lyon_events(path.iter())
+        }
+    }
+
+    #[test]
+    fn lyon_round_trip() {
+        let p = CGPoint::new;
+        let elements = vec![
+            PathElement::MoveTo(p(0.0, 0.0)),
+            PathElement::LineTo(p(1.0, 0.0)),
+            PathElement::QuadTo(p(2.0, 1.0), p(1.0, 2.0)),
+            PathElement::Close,
+            PathElement::MoveTo(p(5.0, 5.0)),
+            PathElement::CubicTo(p(6.0, 5.0), p(7.0, 6.0), p(7.5, 7.0)),
+        ];
+        let path = CGPath::from_elements(elements.clone());
+
+        let events = path.lyon_events();
+        assert_eq!(events[0], PathEvent::Begin { at: point(0.0, 0.0) });
+        assert_eq!(events[3], PathEvent::End {
+            last: point(1.0, 2.0),
+            first: point(0.0, 0.0),
+            close: true,
+        });
+        assert_eq!(events[6], PathEvent::End {
+            last: point(7.5, 7.0),
+            first: point(5.0, 5.0),
+            close: false,
+        });
+        assert_eq!(events.len(), 7);
+
+        let lyon_path = Path::from(&*path);
+        assert_eq!(lyon_path.iter().collect::<Vec<_>>(), events);
+        assert_eq!(CGPath::from(&lyon_path).elements(), elements);
+
+        // Segments after a close begin again at the start of the closed subpath.
+        let reopened = CGPath::from_elements(vec![
+            PathElement::MoveTo(p(1.0, 1.0)),
+            PathElement::LineTo(p(2.0, 1.0)),
+            PathElement::Close,
+            PathElement::LineTo(p(1.0, 3.0)),
+        ]);
+        let events = reopened.lyon_events();
+        assert_eq!(events[3], PathEvent::Begin { at: point(1.0, 1.0) });
+        assert_eq!(events[4], PathEvent::Line { from: point(1.0, 1.0), to: point(1.0, 3.0) });
+    }
+}
@@ -7,6 +7,9 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+// Struct literals spell out `field: field` throughout the crate.
+#![allow(clippy::redundant_field_names)]
+
 extern crate libc;
 extern crate core_foundation;
 
@@ -14,12 +17,26 @@ extern crate core_foundation;
 #[cfg(target_os = "macos")]
 extern crate bitflags;
 
-#[macro_use]
+#[cfg_attr(target_os = "macos", macro_use)]
 extern crate foreign_types;
 
+#[cfg(feature = "euclid")]
+extern crate euclid;
+#[cfg(feature = "lyon_path")]
+extern crate lyon_path;
+#[cfg(feature = "mint")]
+extern crate mint;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+
 pub mod base;
 pub mod color_space;
+#[cfg(target_os = "macos")]
 pub mod context;
+#[cfg(target_os = "macos")]
 pub mod data_provider;
 #[cfg(target_os = "macos")]
 pub mod display;
@@ -27,10 +44,19 @@ pub mod display;
 pub mod event;
 #[cfg(target_os = "macos")]
 pub mod event_source;
+#[cfg(target_os = "macos")]
 pub mod font;
 pub mod geometry;
 #[cfg(target_os = "macos")]
 pub mod private;
 pub mod image;
+#[cfg(any(feature = "euclid", feature = "lyon_path", feature = "mint"))]
+mod interop;
 pub mod path;
+pub mod path_encoding;
+pub mod pixel;
+pub mod region;
+pub mod svg_path;
+pub mod typed_geometry;
+#[cfg(target_os = "macos")]
 mod sys;
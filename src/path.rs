@@ -7,41 +7,1798 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use core_foundation::base::{CFRelease, CFRetain, CFTypeID};
-use foreign_types::ForeignType;
-use geometry::CGPoint;
-use libc::c_void;
+//! Paths.
+//!
+//! On macOS, `CGPath` and `CGMutablePath` wrap the framework objects. Everywhere else they are
+//! backed by a plain list of `PathElement`s, so that code which builds and inspects paths can be
+//! compiled and tested on any platform. Both backends produce the same elements for the same
+//! sequence of `CGMutablePath` calls.
+
+use base::CGFloat;
+use geometry::{CG_RECT_NULL, CGAffineTransform, CGPoint, CGRect, CGSize};
+use std::f64::consts::PI as PI_F64;
 use std::fmt::{self, Debug, Formatter};
-use std::marker::PhantomData;
 use std::ops::Deref;
 use std::slice;
 
-foreign_type! {
-    #[doc(hidden)]
-    type CType = ::sys::CGPath;
-    fn drop = |p| CFRelease(p as *mut _);
-    fn clone = |p| CFRetain(p as *const _) as *mut _;
-    pub struct CGPath;
-    pub struct CGPathRef;
-}
+#[cfg(target_os = "macos")]
+use core_foundation::base::{CFRelease, CFRetain, CFTypeID};
+#[cfg(target_os = "macos")]
+use foreign_types::{ForeignType, ForeignTypeRef};
+#[cfg(target_os = "macos")]
+use libc::c_void;
+#[cfg(target_os = "macos")]
+use std::marker::PhantomData;
+#[cfg(target_os = "macos")]
+use std::ptr;
+
+#[cfg(not(target_os = "macos"))]
+use std::borrow::Borrow;
+
+mod boolean;
+
+const PI: CGFloat = PI_F64 as CGFloat;
+
+/// The distance of the control points from the end points of a cubic Bézier curve
+/// approximating a quarter circle of radius 1.
+const QUARTER_CIRCLE_KAPPA: CGFloat = 0.552_284_749_830_793_4;
+
+#[cfg(target_os = "macos")]
+foreign_type! {
+    #[doc(hidden)]
+    type CType = ::sys::CGPath;
+    fn drop = |p| CFRelease(p as *mut _);
+    fn clone = |p| CFRetain(p as *const _) as *mut _;
+    pub struct CGPath;
+    pub struct CGPathRef;
+}
+
+#[cfg(target_os = "macos")]
+impl CGPath {
+    pub fn type_id() -> CFTypeID {
+        unsafe {
+            CGPathGetTypeID()
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl CGPathRef {
+    pub fn apply<'a, F>(&'a self, mut closure: &'a F) where F: FnMut(CGPathElementRef<'a>) {
+        unsafe {
+            CGPathApply(self.as_ptr(), &mut closure as *mut _ as *mut c_void, do_apply::<F>);
+        }
+
+        unsafe extern "C" fn do_apply<'a, F>(info: *mut c_void, element: *const CGPathElement)
+                                             where F: FnMut(CGPathElementRef<'a>) {
+            let closure = info as *mut *mut F;
+            (**closure)(CGPathElementRef::new(element))
+        }
+    }
+
+    /// Returns the elements of the path, in order.
+    pub fn elements(&self) -> Vec<PathElement> {
+        let mut elements = vec![];
+        unsafe {
+            CGPathApply(self.as_ptr(), &mut elements as *mut _ as *mut c_void, push_element);
+        }
+        return elements;
+
+        unsafe extern "C" fn push_element(info: *mut c_void, element: *const CGPathElement) {
+            let elements = &mut *(info as *mut Vec<PathElement>);
+            elements.push(PathElement::from(&*element))
+        }
+    }
+}
+
+/// An immutable path, stored as a list of elements.
+#[cfg(not(target_os = "macos"))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CGPath {
+    elements: Vec<PathElement>,
+}
+
+/// A borrowed `CGPath`.
+#[cfg(not(target_os = "macos"))]
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct CGPathRef {
+    elements: [PathElement],
+}
+
+#[cfg(not(target_os = "macos"))]
+impl Deref for CGPath {
+    type Target = CGPathRef;
+
+    #[inline]
+    fn deref(&self) -> &CGPathRef {
+        // `CGPathRef` is a single-field struct around the slice, so it has the same layout.
+        unsafe {
+            &*(&self.elements[..] as *const [PathElement] as *const CGPathRef)
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+impl AsRef<CGPathRef> for CGPath {
+    #[inline]
+    fn as_ref(&self) -> &CGPathRef {
+        self
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+impl Borrow<CGPathRef> for CGPath {
+    #[inline]
+    fn borrow(&self) -> &CGPathRef {
+        self
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+impl ToOwned for CGPathRef {
+    type Owned = CGPath;
+
+    #[inline]
+    fn to_owned(&self) -> CGPath {
+        CGPath {
+            elements: self.elements.to_vec(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+impl CGPathRef {
+    /// Returns the elements of the path, in order.
+    #[inline]
+    pub fn elements(&self) -> Vec<PathElement> {
+        self.elements.to_vec()
+    }
+}
+
+/// A single segment of a path, with the points it carries.
+///
+/// The end point of each segment is its last point; the start point is the end point of the
+/// previous segment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathElement {
+    MoveTo(CGPoint),
+    LineTo(CGPoint),
+    /// A quadratic Bézier curve with one control point.
+    QuadTo(CGPoint, CGPoint),
+    /// A cubic Bézier curve with two control points.
+    CubicTo(CGPoint, CGPoint, CGPoint),
+    /// A straight line back to the start of the current subpath, which ends it.
+    Close,
+}
+
+impl PathElement {
+    /// Returns the point this element ends at, or `None` for `Close`, which ends at the start of
+    /// its subpath.
+    #[inline]
+    pub fn end_point(&self) -> Option<CGPoint> {
+        match *self {
+            PathElement::MoveTo(p) |
+            PathElement::LineTo(p) |
+            PathElement::QuadTo(_, p) |
+            PathElement::CubicTo(_, _, p) => Some(p),
+            PathElement::Close => None,
+        }
+    }
+
+    /// Returns this element with all of its points transformed by `t`.
+    pub fn apply_transform(&self, t: &CGAffineTransform) -> PathElement {
+        match *self {
+            PathElement::MoveTo(p) => PathElement::MoveTo(p.apply_transform(t)),
+            PathElement::LineTo(p) => PathElement::LineTo(p.apply_transform(t)),
+            PathElement::QuadTo(c, p) => {
+                PathElement::QuadTo(c.apply_transform(t), p.apply_transform(t))
+            }
+            PathElement::CubicTo(c1, c2, p) => {
+                PathElement::CubicTo(c1.apply_transform(t),
+                                     c2.apply_transform(t),
+                                     p.apply_transform(t))
+            }
+            PathElement::Close => PathElement::Close,
+        }
+    }
+}
+
+impl<'a> From<&'a CGPathElement> for PathElement {
+    fn from(element: &'a CGPathElement) -> PathElement {
+        let points = element.points();
+        match element.element_type {
+            CGPathElementType::MoveToPoint => PathElement::MoveTo(points[0]),
+            CGPathElementType::AddLineToPoint => PathElement::LineTo(points[0]),
+            CGPathElementType::AddQuadCurveToPoint => PathElement::QuadTo(points[0], points[1]),
+            CGPathElementType::AddCurveToPoint => {
+                PathElement::CubicTo(points[0], points[1], points[2])
+            }
+            CGPathElementType::CloseSubpath => PathElement::Close,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl CGPathRef {
+    /// Returns the smallest rectangle containing all points of all elements, including control
+    /// points, or the null rectangle if the path is empty.
+    pub fn bounding_box(&self) -> CGRect {
+        unsafe {
+            CGPathGetBoundingBox(self.as_ptr())
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+impl CGPathRef {
+    /// Returns the smallest rectangle containing all points of all elements, including control
+    /// points, or the null rectangle if the path is empty.
+    pub fn bounding_box(&self) -> CGRect {
+        let mut bounds = Bounds::new();
+        for element in &self.elements {
+            match *element {
+                PathElement::MoveTo(p) | PathElement::LineTo(p) => bounds.add(p),
+                PathElement::QuadTo(c, p) => {
+                    bounds.add(c);
+                    bounds.add(p);
+                }
+                PathElement::CubicTo(c1, c2, p) => {
+                    bounds.add(c1);
+                    bounds.add(c2);
+                    bounds.add(p);
+                }
+                PathElement::Close => {}
+            }
+        }
+        bounds.to_rect()
+    }
+}
+
+impl CGPathRef {
+    /// Returns the smallest rectangle containing every point on the path, or the null rectangle
+    /// if the path is empty.
+    ///
+    /// Unlike `bounding_box`, control points are only included where the curve actually reaches
+    /// them. This is computed from the elements on all platforms, by solving for the extrema of
+    /// each curve.
+    pub fn path_bounding_box(&self) -> CGRect {
+        let mut bounds = Bounds::new();
+        let mut current = CGPoint::new(0.0, 0.0);
+        for element in self.elements() {
+            match element {
+                PathElement::MoveTo(p) | PathElement::LineTo(p) => bounds.add(p),
+                PathElement::QuadTo(c, p) => {
+                    // The derivative of each coordinate is linear in t.
+                    let extrema = |p0: CGFloat, c: CGFloat, p1: CGFloat| {
+                        let denominator = p0 - 2.0 * c + p1;
+                        if denominator == 0.0 { None } else { Some((p0 - c) / denominator) }
+                    };
+                    let roots = extrema(current.x, c.x, p.x).into_iter()
+                                                              .chain(extrema(current.y, c.y, p.y));
+                    for t in roots.filter(|&t| t > 0.0 && t < 1.0) {
+                        bounds.add(quad_point(current, c, p, t));
+                    }
+                    bounds.add(p);
+                }
+                PathElement::CubicTo(c1, c2, p) => {
+                    let mut roots = cubic_extrema(current.x, c1.x, c2.x, p.x);
+                    roots.extend(cubic_extrema(current.y, c1.y, c2.y, p.y));
+                    for t in roots {
+                        bounds.add(cubic_point(current, c1, c2, p, t));
+                    }
+                    bounds.add(p);
+                }
+                PathElement::Close => {}
+            }
+            if let Some(end) = element.end_point() {
+                current = end;
+            }
+        }
+        bounds.to_rect()
+    }
+}
+
+impl CGPathRef {
+    /// Approximates the path by straight line segments, one polyline per subpath.
+    ///
+    /// No point of the approximation is further than `tolerance` from the curve it replaces.
+    /// Each curve is split into a number of equal parameter steps computed from its control
+    /// points, so the output only depends on the elements and the tolerance. Subpaths made of a
+    /// single point are omitted.
+    ///
+    /// Curves are split into at most 1024 steps, which bounds the work for huge curves but can
+    /// leave points further than `tolerance` from them.
+    ///
+    /// Panics if `tolerance` is not positive.
+    pub fn flatten(&self, tolerance: CGFloat) -> Vec<Polyline> {
+        assert!(tolerance > 0.0, "the flattening tolerance must be positive");
+        let mut polylines = vec![];
+        let mut current = Polyline {
+            points: vec![],
+            closed: false,
+        };
+        let mut start = CGPoint::new(0.0, 0.0);
+        let mut last = start;
+        for element in self.elements() {
+            if let PathElement::MoveTo(p) = element {
+                finish_polyline(&mut polylines, &mut current);
+                current.points.push(p);
+                start = p;
+                last = p;
+                continue;
+            }
+            if current.closed {
+                // A segment after a close implicitly starts again from the subpath's start.
+                finish_polyline(&mut polylines, &mut current);
+                current.points.push(start);
+            }
+            match element {
+                PathElement::MoveTo(_) => unreachable!(),
+                PathElement::LineTo(p) => current.points.push(p),
+                PathElement::QuadTo(c, p) => {
+                    let deviation = distance(last.x - 2.0 * c.x + p.x, last.y - 2.0 * c.y + p.y);
+                    let steps = flattening_steps(deviation / 4.0, tolerance);
+                    for i in 1..steps + 1 {
+                        let t = i as CGFloat / steps as CGFloat;
+                        current.points.push(quad_point(last, c, p, t));
+                    }
+                }
+                PathElement::CubicTo(c1, c2, p) => {
+                    let deviation =
+                        distance(last.x - 2.0 * c1.x + c2.x, last.y - 2.0 * c1.y + c2.y).max(
+                            distance(c1.x - 2.0 * c2.x + p.x, c1.y - 2.0 * c2.y + p.y));
+                    let steps = flattening_steps(deviation * 0.75, tolerance);
+                    for i in 1..steps + 1 {
+                        let t = i as CGFloat / steps as CGFloat;
+                        current.points.push(cubic_point(last, c1, c2, p, t));
+                    }
+                }
+                PathElement::Close => {
+                    current.closed = true;
+                    last = start;
+                    continue;
+                }
+            }
+            last = element.end_point().unwrap();
+        }
+        finish_polyline(&mut polylines, &mut current);
+        polylines
+    }
+}
+
+impl CGPathRef {
+    /// Returns true if `point` is inside the area the path would fill with `rule`, after
+    /// applying `transform` to the path.
+    ///
+    /// Open subpaths are implicitly closed, as when filling. This is computed from the elements
+    /// by intersecting a horizontal ray with each segment, solving curves exactly rather than
+    /// flattening them, and matches `CGPathContainsPoint` away from the path's edges.
+    pub fn contains_point(&self,
+                          point: CGPoint,
+                          transform: Option<&CGAffineTransform>,
+                          rule: CGPathFillRule)
+                          -> bool {
+        // Transforming the point back is equivalent to transforming every element.
+        let point = match transform {
+            Some(t) => match t.checked_invert() {
+                Some(inverse) => point.apply_transform(&inverse),
+                None => return false,
+            },
+            None => point,
+        };
+
+        let winding = self.winding_number(point);
+        match rule {
+            CGPathFillRule::Winding => winding != 0,
+            CGPathFillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+
+    /// Returns the sum of the directions of the edges crossing the horizontal ray from `point`
+    /// towards positive x, counting upward edges as 1 and downward ones as -1.
+    fn winding_number(&self, point: CGPoint) -> i32 {
+        let mut winding = 0;
+        let mut start = CGPoint::new(0.0, 0.0);
+        let mut last = start;
+        for element in self.elements() {
+            match element {
+                PathElement::MoveTo(p) => {
+                    winding += line_crossings(last, start, point);
+                    start = p;
+                }
+                PathElement::LineTo(p) => winding += line_crossings(last, p, point),
+                PathElement::QuadTo(c, p) => {
+                    let denominator = last.y - 2.0 * c.y + p.y;
+                    let mut splits = vec![0.0];
+                    if denominator != 0.0 {
+                        let t = (last.y - c.y) / denominator;
+                        if t > 0.0 && t < 1.0 {
+                            splits.push(t);
+                        }
+                    }
+                    splits.push(1.0);
+                    winding += curve_crossings(|t| quad_point(last, c, p, t), &splits, point);
+                }
+                PathElement::CubicTo(c1, c2, p) => {
+                    let mut splits = cubic_extrema(last.y, c1.y, c2.y, p.y);
+                    splits.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                    splits.insert(0, 0.0);
+                    splits.push(1.0);
+                    winding += curve_crossings(|t| cubic_point(last, c1, c2, p, t),
+                                               &splits,
+                                               point);
+                }
+                PathElement::Close => {
+                    winding += line_crossings(last, start, point);
+                    last = start;
+                }
+            }
+            if let Some(end) = element.end_point() {
+                last = end;
+            }
+        }
+        winding + line_crossings(last, start, point)
+    }
+}
+
+/// How the inside of a path is determined when filling it or testing whether it contains a
+/// point.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CGPathFillRule {
+    /// A point is inside if the path winds around it a non-zero number of times.
+    Winding = 0,
+    /// A point is inside if a ray from it crosses the path an odd number of times.
+    EvenOdd = 1,
+}
+
+/// Returns the winding contribution of the line from `from` to `to` for the ray from `point`.
+///
+/// An edge covers the y range from its lower end inclusive to its upper end exclusive, so a ray
+/// through a vertex is counted once.
+fn line_crossings(from: CGPoint, to: CGPoint, point: CGPoint) -> i32 {
+    let (direction, low, high) = if from.y < to.y {
+        (1, from, to)
+    } else if from.y > to.y {
+        (-1, to, from)
+    } else {
+        return 0;
+    };
+    if point.y < low.y || point.y >= high.y {
+        return 0;
+    }
+    let x = low.x + (point.y - low.y) / (high.y - low.y) * (high.x - low.x);
+    if x > point.x { direction } else { 0 }
+}
+
+/// Returns the winding contribution of a curve for the ray from `point`, given the parameters
+/// that split the curve into pieces whose y coordinate is monotonic.
+fn curve_crossings<F>(curve: F, splits: &[CGFloat], point: CGPoint) -> i32
+                      where F: Fn(CGFloat) -> CGPoint {
+    let mut winding = 0;
+    for pair in splits.windows(2) {
+        let (from, to) = (curve(pair[0]), curve(pair[1]));
+        let (direction, low, high) = if from.y < to.y {
+            (1, pair[0], pair[1])
+        } else if from.y > to.y {
+            (-1, pair[1], pair[0])
+        } else {
+            continue;
+        };
+        if point.y < curve(low).y || point.y >= curve(high).y {
+            continue;
+        }
+
+        // Bisect for the parameter where the piece reaches the ray, with `low` staying below it.
+        let (mut low, mut high) = (low, high);
+        for _ in 0..64 {
+            let mid = (low + high) * 0.5;
+            if curve(mid).y <= point.y {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        if curve((low + high) * 0.5).x > point.x {
+            winding += direction;
+        }
+    }
+    winding
+}
+
+impl CGPathRef {
+    /// Returns the outline of the area covered by stroking the path with the given parameters,
+    /// using `CGPathCreateCopyByStrokingPath`. A non-positive line width gives an empty path.
+    #[cfg(target_os = "macos")]
+    pub fn stroke(&self,
+                  line_width: CGFloat,
+                  cap: CGLineCap,
+                  join: CGLineJoin,
+                  miter_limit: CGFloat)
+                  -> CGPath {
+        if line_width.is_nan() || line_width <= 0.0 {
+            return CGMutablePath::new().into_path();
+        }
+        unsafe {
+            let outline = CGPathCreateCopyByStrokingPath(self.as_ptr(),
+                                                         ptr::null(),
+                                                         line_width,
+                                                         cap,
+                                                         join,
+                                                         miter_limit);
+            if outline.is_null() {
+                CGMutablePath::new().into_path()
+            } else {
+                CGPath::from_ptr(outline)
+            }
+        }
+    }
+
+    /// Returns an approximation of the outline of the area covered by stroking the path with
+    /// the given parameters, for platforms without `CGPathCreateCopyByStrokingPath`.
+    ///
+    /// Unlike the single outline Quartz builds, this is made of overlapping closed pieces, one
+    /// per segment of the flattened path, join and cap, all going counterclockwise (in a y-up
+    /// coordinate system), so it has to be filled or hit-tested with `CGPathFillRule::Winding`.
+    /// Curves are flattened with a tolerance of a hundredth of the line width. Zero-length
+    /// subpaths get round or square caps, but nothing with butt caps. A non-positive line width
+    /// gives an empty path.
+    #[cfg(not(target_os = "macos"))]
+    #[inline]
+    pub fn stroke(&self,
+                  line_width: CGFloat,
+                  cap: CGLineCap,
+                  join: CGLineJoin,
+                  miter_limit: CGFloat)
+                  -> CGPath {
+        self.approximate_stroke(line_width, cap, join, miter_limit)
+    }
+
+    #[cfg(any(test, not(target_os = "macos")))]
+    fn approximate_stroke(&self,
+                          line_width: CGFloat,
+                          cap: CGLineCap,
+                          join: CGLineJoin,
+                          miter_limit: CGFloat)
+                          -> CGPath {
+        let mut outline = CGMutablePath::new();
+        if line_width.is_nan() || line_width <= 0.0 {
+            return outline.into_path();
+        }
+
+        let half_width = line_width * 0.5;
+        for polyline in self.flatten(line_width * 0.01) {
+            let mut points = polyline.points;
+            points.dedup();
+
+            if points.len() == 1 {
+                add_cap(&mut outline, points[0], CGPoint::new(1.0, 0.0), half_width, cap);
+                add_cap(&mut outline, points[0], CGPoint::new(-1.0, 0.0), half_width, cap);
+                continue;
+            }
+
+            // A closed subpath of two points goes there and back, turning at both ends.
+            let count = points.len();
+            let edge_count = if polyline.closed { count } else { count - 1 };
+            for i in 0..edge_count {
+                let (from, to) = (points[i], points[(i + 1) % count]);
+                let offset = scale(left_normal(direction(from, to)), half_width);
+                add_polygon(&mut outline, &[add(from, offset),
+                                            sub(from, offset),
+                                            sub(to, offset),
+                                            add(to, offset)]);
+            }
+
+            let joined = if edge_count == count { 0..count } else { 1..count - 1 };
+            for i in joined {
+                let previous = points[(i + count - 1) % count];
+                let next = points[(i + 1) % count];
+                add_join(&mut outline,
+                         points[i],
+                         direction(previous, points[i]),
+                         direction(points[i], next),
+                         half_width,
+                         join,
+                         miter_limit);
+            }
+
+            if edge_count < count {
+                add_cap(&mut outline, points[0], direction(points[1], points[0]), half_width, cap);
+                add_cap(&mut outline,
+                        points[count - 1],
+                        direction(points[count - 2], points[count - 1]),
+                        half_width,
+                        cap);
+            }
+        }
+        outline.into_path()
+    }
+}
+
+/// How the ends of open subpaths are drawn when stroking.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CGLineCap {
+    /// The stroke ends exactly at the end point.
+    Butt = 0,
+    /// The stroke ends with a half circle around the end point.
+    Round = 1,
+    /// The stroke extends beyond the end point by half the line width.
+    Square = 2,
+}
+
+/// How the corners between connected segments are drawn when stroking.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CGLineJoin {
+    /// The outer edges are extended until they meet, unless that is further than the miter
+    /// limit times the line width from the corner, in which case the corner is beveled.
+    Miter = 0,
+    /// The corner is rounded off with a circle around it.
+    Round = 1,
+    /// The corner is cut off with a straight line between the outer edges.
+    Bevel = 2,
+}
+
+/// Adds the cap at `point` for a subpath leaving it in `outward` direction.
+#[cfg(any(test, not(target_os = "macos")))]
+fn add_cap(outline: &mut CGMutablePath,
+           point: CGPoint,
+           outward: CGPoint,
+           half_width: CGFloat,
+           cap: CGLineCap) {
+    match cap {
+        CGLineCap::Butt => {}
+        CGLineCap::Round => add_circle(outline, point, half_width),
+        CGLineCap::Square => {
+            let side = scale(left_normal(outward), half_width);
+            let end = add(point, scale(outward, half_width));
+            add_polygon(outline,
+                        &[add(point, side), sub(point, side), sub(end, side), add(end, side)]);
+        }
+    }
+}
+
+/// Adds the join at `point` between a segment arriving in direction `incoming` and one leaving
+/// in direction `outgoing`.
+#[cfg(any(test, not(target_os = "macos")))]
+fn add_join(outline: &mut CGMutablePath,
+            point: CGPoint,
+            incoming: CGPoint,
+            outgoing: CGPoint,
+            half_width: CGFloat,
+            join: CGLineJoin,
+            miter_limit: CGFloat) {
+    let cross = incoming.x * outgoing.y - incoming.y * outgoing.x;
+    let dot = incoming.x * outgoing.x + incoming.y * outgoing.y;
+    if cross == 0.0 && dot > 0.0 {
+        return;
+    }
+    if join == CGLineJoin::Round {
+        return add_circle(outline, point, half_width);
+    }
+
+    // The gap to fill is on the outside of the turn, i.e. on the right for a left turn.
+    let side = if cross > 0.0 { -half_width } else { half_width };
+    let before = add(point, scale(left_normal(incoming), side));
+    let after = add(point, scale(left_normal(outgoing), side));
+
+    // The miter tip is `1 / cos(turn / 2)` half widths from the corner, and cos²(turn / 2)
+    // is `(1 + dot) / 2`.
+    let miter_ratio_squared = 2.0 / (1.0 + dot);
+    if join == CGLineJoin::Miter && dot > -1.0 && miter_ratio_squared <= miter_limit * miter_limit {
+        let tip = add(point, scale(sub(add(before, after), scale(point, 2.0)), 1.0 / (1.0 + dot)));
+        add_polygon(outline, &[point, before, tip, after]);
+    } else {
+        add_polygon(outline, &[point, before, after]);
+    }
+}
+
+/// Adds a closed polygon, reversing it if needed so that it goes counterclockwise.
+#[cfg(any(test, not(target_os = "macos")))]
+fn add_polygon(outline: &mut CGMutablePath, points: &[CGPoint]) {
+    let doubled_area: CGFloat = points.iter().zip(points.iter().cycle().skip(1)).map(|(a, b)| {
+        a.x * b.y - b.x * a.y
+    }).sum();
+    let mut ordered = points.to_vec();
+    if doubled_area < 0.0 {
+        ordered.reverse();
+    }
+    outline.move_to(ordered[0]);
+    for &point in &ordered[1..] {
+        outline.line_to(point);
+    }
+    outline.close();
+}
+
+#[cfg(any(test, not(target_os = "macos")))]
+fn add_circle(outline: &mut CGMutablePath, center: CGPoint, radius: CGFloat) {
+    let corner = CGPoint::new(center.x - radius, center.y - radius);
+    outline.add_ellipse(CGRect::new(&corner, &CGSize::new(radius * 2.0, radius * 2.0)));
+}
+
+#[inline]
+fn add(a: CGPoint, b: CGPoint) -> CGPoint {
+    CGPoint::new(a.x + b.x, a.y + b.y)
+}
+
+#[inline]
+fn sub(a: CGPoint, b: CGPoint) -> CGPoint {
+    CGPoint::new(a.x - b.x, a.y - b.y)
+}
+
+#[inline]
+fn scale(a: CGPoint, factor: CGFloat) -> CGPoint {
+    CGPoint::new(a.x * factor, a.y * factor)
+}
+
+/// Returns the unit vector from `from` towards `to`, which must be different points.
+#[inline]
+fn direction(from: CGPoint, to: CGPoint) -> CGPoint {
+    let d = sub(to, from);
+    scale(d, 1.0 / distance(d.x, d.y))
+}
+
+/// Returns `v` rotated by a quarter turn counterclockwise (in a y-up coordinate system).
+#[inline]
+fn left_normal(v: CGPoint) -> CGPoint {
+    CGPoint::new(-v.y, v.x)
+}
+
+impl CGPathRef {
+    /// Returns the "on" parts of the path for the dash pattern `lengths` started at `phase`,
+    /// like `CGPathCreateCopyByDashingPath`.
+    ///
+    /// The pattern alternates between lengths that are drawn and lengths that are skipped,
+    /// starting with a drawn one, and restarts at `phase` for every subpath. Closed subpaths are
+    /// dashed along their closing line too, and each dash becomes an open subpath. Curves are
+    /// split where dashes start and end, at positions measured by arc length, so the result
+    /// keeps their shape exactly. A zero length dash gives a subpath of one point, which shows
+    /// up with round or square caps. If the pattern has a negative length or no positive ones,
+    /// the path is returned unchanged.
+    pub fn dash(&self, phase: CGFloat, lengths: &[CGFloat]) -> CGPath {
+        let elements = self.elements();
+        let total: CGFloat = lengths.iter().sum();
+        if lengths.iter().any(|&length| length < 0.0) || !total.is_finite() || total <= 0.0 {
+            return CGPath::from_elements(elements);
+        }
+
+        let mut dashed = CGMutablePath::new();
+        for subpath in subpaths(&elements) {
+            let mut dasher = Dasher::new(lengths, phase, total);
+            for segment in &subpath.segments {
+                dasher.dash_segment(segment, &mut dashed);
+            }
+            // Zero-length dashes at the very end would duplicate the ones at the start of a
+            // closed subpath.
+            if !subpath.closed {
+                let end = subpath.segments[subpath.segments.len() - 1].end();
+                while dasher.remaining <= 0.0 {
+                    dasher.advance(end, &mut dashed);
+                }
+            }
+        }
+        dashed.into_path()
+    }
+}
+
+/// How precisely dashing measures curves.
+const DASH_LENGTH_TOLERANCE: CGFloat = 1e-6;
+
+/// The position within a dash pattern while walking along a subpath.
+struct Dasher<'a> {
+    lengths: &'a [CGFloat],
+    index: usize,
+    /// How much of the current dash or gap is left.
+    remaining: CGFloat,
+    /// Whether the current dash has been started in the output.
+    drawing: bool,
+}
+
+impl<'a> Dasher<'a> {
+    fn new(lengths: &'a [CGFloat], phase: CGFloat, total: CGFloat) -> Dasher<'a> {
+        let mut offset = phase % total;
+        if offset < 0.0 {
+            offset += total;
+        }
+        let mut index = 0;
+        while offset >= lengths[index] && offset > 0.0 {
+            offset -= lengths[index];
+            index = (index + 1) % lengths.len();
+        }
+        Dasher {
+            lengths: lengths,
+            index: index,
+            remaining: lengths[index] - offset,
+            drawing: false,
+        }
+    }
+
+    #[inline]
+    fn is_on(&self) -> bool {
+        self.index.is_multiple_of(2)
+    }
+
+    /// Moves on to the next dash or gap, leaving a dot at `point` if the current dash is empty.
+    fn advance(&mut self, point: CGPoint, output: &mut CGMutablePath) {
+        if self.is_on() && !self.drawing {
+            output.move_to(point);
+            output.line_to(point);
+        }
+        self.index = (self.index + 1) % self.lengths.len();
+        self.remaining = self.lengths[self.index];
+        self.drawing = false;
+    }
+
+    fn dash_segment(&mut self, segment: &Segment, output: &mut CGMutablePath) {
+        let total = segment.length(DASH_LENGTH_TOLERANCE);
+        let mut position = 0.0;
+        let mut t = 0.0;
+        while position < total {
+            if self.remaining <= 0.0 {
+                self.advance(segment.point_at(t), output);
+                continue;
+            }
+
+            let step = self.remaining.min(total - position);
+            let end_t = segment.t_at_length(position + step, total, DASH_LENGTH_TOLERANCE);
+            if self.is_on() {
+                let piece = segment.sub_segment(t, end_t);
+                if !self.drawing {
+                    output.move_to(piece.start());
+                    self.drawing = true;
+                }
+                output.add_element(piece.to_element());
+            }
+            position += step;
+            t = end_t;
+            self.remaining -= step;
+        }
+    }
+}
+
+impl CGPathRef {
+    /// Measures the path for looking up points and pieces of it by distance along it.
+    ///
+    /// Lengths of curves are computed to within about `tolerance`, which is also how closely
+    /// distances passed to the returned measure are located on them. Closed subpaths include the
+    /// closing line, and subpaths follow each other without gaps in the distances.
+    pub fn measure(&self, tolerance: CGFloat) -> PathMeasure {
+        let mut segments = vec![];
+        let mut length = 0.0;
+        for (index, subpath) in subpaths(&self.elements()).into_iter().enumerate() {
+            for segment in subpath.segments {
+                let segment_length = segment.length(tolerance);
+                if segment_length > 0.0 {
+                    segments.push(MeasuredSegment {
+                        segment: segment,
+                        subpath: index,
+                        start: length,
+                        length: segment_length,
+                    });
+                    length += segment_length;
+                }
+            }
+        }
+        PathMeasure {
+            segments: segments,
+            length: length,
+            tolerance: tolerance,
+        }
+    }
+}
+
+/// Distances along a path, as returned by `CGPathRef::measure`.
+pub struct PathMeasure {
+    segments: Vec<MeasuredSegment>,
+    length: CGFloat,
+    tolerance: CGFloat,
+}
+
+struct MeasuredSegment {
+    segment: Segment,
+    subpath: usize,
+    start: CGFloat,
+    length: CGFloat,
+}
+
+/// A point on a path and the direction of the path there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathPosition {
+    pub point: CGPoint,
+    /// The angle of the tangent in radians, counterclockwise from the positive x axis (in a
+    /// y-up coordinate system).
+    pub angle: CGFloat,
+}
+
+impl PathMeasure {
+    /// Returns the total length of the path.
+    #[inline]
+    pub fn length(&self) -> CGFloat {
+        self.length
+    }
+
+    /// Returns the point at `distance` along the path and the direction of the path there, or
+    /// `None` if the path has no length or `distance` is NaN. Distances outside the path are
+    /// clamped to its ends.
+    pub fn position_at(&self, distance: CGFloat) -> Option<PathPosition> {
+        if distance.is_nan() {
+            return None;
+        }
+        let measured = &self.segments[self.segment_at(distance)?];
+        let t = measured.segment.t_at_length(distance - measured.start,
+                                             measured.length,
+                                             self.tolerance);
+        let tangent = measured.segment.tangent_at(t);
+        Some(PathPosition {
+            point: measured.segment.point_at(t),
+            angle: tangent.y.atan2(tangent.x),
+        })
+    }
+
+    /// Returns the part of the path between the distances `from` and `to`, which are clamped to
+    /// the path. Curves are split rather than flattened, and where the part crosses from one
+    /// subpath to the next, it starts a new subpath too.
+    pub fn sub_path(&self, from: CGFloat, to: CGFloat) -> CGPath {
+        let mut path = CGMutablePath::new();
+        // `max` and `min` also replace NaN distances with the ends of the path.
+        let (from, to) = (from.max(0.0), to.min(self.length));
+        if from >= to {
+            return path.into_path();
+        }
+
+        let mut last_subpath = None;
+        for measured in &self.segments {
+            let end = measured.start + measured.length;
+            if end <= from || measured.start >= to {
+                continue;
+            }
+            let t0 = measured.segment.t_at_length(from - measured.start,
+                                                  measured.length,
+                                                  self.tolerance);
+            let t1 = measured.segment.t_at_length(to - measured.start,
+                                                  measured.length,
+                                                  self.tolerance);
+            let piece = measured.segment.sub_segment(t0, t1);
+            if last_subpath != Some(measured.subpath) {
+                path.move_to(piece.start());
+                last_subpath = Some(measured.subpath);
+            }
+            path.add_element(piece.to_element());
+        }
+        path.into_path()
+    }
+
+    /// Returns the index of the segment containing `distance`, preferring the later one at a
+    /// boundary between segments.
+    fn segment_at(&self, distance: CGFloat) -> Option<usize> {
+        if self.segments.is_empty() {
+            return None;
+        }
+        let index = self.segments.iter()
+                                 .position(|measured| measured.start > distance)
+                                 .unwrap_or(self.segments.len());
+        Some(index.max(1) - 1)
+    }
+}
+
+/// A subpath approximated by straight line segments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyline {
+    /// The vertices in order. For a closed polyline, the first point is not repeated at the end.
+    pub points: Vec<CGPoint>,
+    /// Whether the subpath was closed, i.e. has an implicit segment from the last point back to
+    /// the first.
+    pub closed: bool,
+}
+
+fn finish_polyline(polylines: &mut Vec<Polyline>, current: &mut Polyline) {
+    let mut points = current.points.split_off(0);
+    // A closed subpath that draws back to its start before closing would repeat it.
+    while current.closed && points.len() > 1 && points[0] == points[points.len() - 1] {
+        points.pop();
+    }
+    if points.len() > 1 {
+        polylines.push(Polyline {
+            points: points,
+            closed: current.closed,
+        });
+    }
+    current.closed = false;
+}
+
+/// Returns the number of equal parameter steps needed to flatten a curve whose chords deviate
+/// from it by at most `max_deviation / n²` when split into `n` steps.
+fn flattening_steps(max_deviation: CGFloat, tolerance: CGFloat) -> usize {
+    // Bounds the work done for huge curves, as documented by `flatten`.
+    const MAX_STEPS: CGFloat = 1024.0;
+
+    let steps = (max_deviation / tolerance).sqrt().ceil();
+    if steps.is_nan() {
+        // Only reached with non-finite control points or an infinite tolerance.
+        1
+    } else {
+        steps.clamp(1.0, MAX_STEPS) as usize
+    }
+}
+
+#[inline]
+fn distance(dx: CGFloat, dy: CGFloat) -> CGFloat {
+    (dx * dx + dy * dy).sqrt()
+}
+
+impl CGPath {
+    /// Builds a path from a sequence of elements, e.g. ones returned by `elements` and then
+    /// modified.
+    pub fn from_elements<I>(elements: I) -> CGPath where I: IntoIterator<Item = PathElement> {
+        let mut path = CGMutablePath::new();
+        for element in elements {
+            path.add_element(element);
+        }
+        path.into_path()
+    }
+}
+
+/// A path that can be added to.
+///
+/// Segments added while there is no current point are ignored, as in Quartz. After a subpath
+/// is closed, adding a segment implicitly moves back to the start of that subpath.
+pub struct CGMutablePath {
+    path: CGPath,
+    #[cfg(not(target_os = "macos"))]
+    subpath_start: CGPoint,
+    #[cfg(not(target_os = "macos"))]
+    current_point: Option<CGPoint>,
+}
+
+impl Deref for CGMutablePath {
+    type Target = CGPathRef;
+
+    #[inline]
+    fn deref(&self) -> &CGPathRef {
+        &self.path
+    }
+}
+
+impl Default for CGMutablePath {
+    #[inline]
+    fn default() -> CGMutablePath {
+        CGMutablePath::new()
+    }
+}
+
+impl CGMutablePath {
+    #[cfg(target_os = "macos")]
+    pub fn new() -> CGMutablePath {
+        unsafe {
+            CGMutablePath {
+                path: CGPath::from_ptr(CGPathCreateMutable()),
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn new() -> CGMutablePath {
+        CGMutablePath {
+            path: CGPath::default(),
+            subpath_start: CGPoint::new(0.0, 0.0),
+            current_point: None,
+        }
+    }
+
+    /// Returns the finished path.
+    #[inline]
+    pub fn into_path(self) -> CGPath {
+        self.path
+    }
+
+    /// Returns the end point of the last segment, or `None` if the path is empty.
+    #[cfg(target_os = "macos")]
+    pub fn current_point(&self) -> Option<CGPoint> {
+        unsafe {
+            if CGPathIsEmpty(self.path.as_ptr()) {
+                None
+            } else {
+                Some(CGPathGetCurrentPoint(self.path.as_ptr()))
+            }
+        }
+    }
+
+    /// Returns the end point of the last segment, or `None` if the path is empty.
+    #[cfg(not(target_os = "macos"))]
+    #[inline]
+    pub fn current_point(&self) -> Option<CGPoint> {
+        self.current_point
+    }
+
+    /// Starts a new subpath at `point`.
+    #[cfg(target_os = "macos")]
+    pub fn move_to(&mut self, point: CGPoint) {
+        unsafe {
+            CGPathMoveToPoint(self.as_mut_ptr(), ptr::null(), point.x, point.y)
+        }
+    }
+
+    /// Starts a new subpath at `point`.
+    #[cfg(not(target_os = "macos"))]
+    pub fn move_to(&mut self, point: CGPoint) {
+        self.path.elements.push(PathElement::MoveTo(point));
+        self.subpath_start = point;
+        self.current_point = Some(point);
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn line_to(&mut self, point: CGPoint) {
+        unsafe {
+            CGPathAddLineToPoint(self.as_mut_ptr(), ptr::null(), point.x, point.y)
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn line_to(&mut self, point: CGPoint) {
+        self.push_segment(PathElement::LineTo(point), point)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn quad_to(&mut self, control: CGPoint, point: CGPoint) {
+        unsafe {
+            CGPathAddQuadCurveToPoint(self.as_mut_ptr(),
+                                      ptr::null(),
+                                      control.x,
+                                      control.y,
+                                      point.x,
+                                      point.y)
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn quad_to(&mut self, control: CGPoint, point: CGPoint) {
+        self.push_segment(PathElement::QuadTo(control, point), point)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn curve_to(&mut self, control1: CGPoint, control2: CGPoint, point: CGPoint) {
+        unsafe {
+            CGPathAddCurveToPoint(self.as_mut_ptr(),
+                                  ptr::null(),
+                                  control1.x,
+                                  control1.y,
+                                  control2.x,
+                                  control2.y,
+                                  point.x,
+                                  point.y)
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    pub fn curve_to(&mut self, control1: CGPoint, control2: CGPoint, point: CGPoint) {
+        self.push_segment(PathElement::CubicTo(control1, control2, point), point)
+    }
+
+    /// Ends the current subpath with a line back to its start.
+    #[cfg(target_os = "macos")]
+    pub fn close(&mut self) {
+        unsafe {
+            CGPathCloseSubpath(self.as_mut_ptr())
+        }
+    }
+
+    /// Ends the current subpath with a line back to its start.
+    #[cfg(not(target_os = "macos"))]
+    pub fn close(&mut self) {
+        let start = self.subpath_start;
+        self.push_segment(PathElement::Close, start)
+    }
+
+    /// Adds `rect` as a closed subpath, going counterclockwise (in a y-up coordinate system)
+    /// from its origin.
+    #[cfg(target_os = "macos")]
+    pub fn add_rect(&mut self, rect: CGRect) {
+        unsafe {
+            CGPathAddRect(self.as_mut_ptr(), ptr::null(), rect)
+        }
+    }
+
+    /// Adds `rect` as a closed subpath, going counterclockwise (in a y-up coordinate system)
+    /// from its origin.
+    #[cfg(not(target_os = "macos"))]
+    pub fn add_rect(&mut self, rect: CGRect) {
+        for element in rect_elements(rect) {
+            self.add_element(element);
+        }
+    }
+
+    /// Adds the ellipse inscribed in `rect` as a closed subpath of four cubic curves, going
+    /// counterclockwise (in a y-up coordinate system) from the middle of the right edge.
+    #[cfg(target_os = "macos")]
+    pub fn add_ellipse(&mut self, rect: CGRect) {
+        unsafe {
+            CGPathAddEllipseInRect(self.as_mut_ptr(), ptr::null(), rect)
+        }
+    }
 
-impl CGPath {
-    pub fn type_id() -> CFTypeID {
+    /// Adds the ellipse inscribed in `rect` as a closed subpath of four cubic curves, going
+    /// counterclockwise (in a y-up coordinate system) from the middle of the right edge.
+    #[cfg(not(target_os = "macos"))]
+    pub fn add_ellipse(&mut self, rect: CGRect) {
+        for element in ellipse_elements(rect) {
+            self.add_element(element);
+        }
+    }
+
+    /// Adds an arc of a circle, from `start_angle` to `end_angle` in radians.
+    ///
+    /// `clockwise` refers to a y-up coordinate system, i.e. clockwise arcs go from the start
+    /// angle towards smaller angles. If the path has a current point, a line is added from it to
+    /// the start of the arc; otherwise a new subpath is started there. The arc is approximated
+    /// by one cubic curve per quarter turn or part thereof.
+    #[cfg(target_os = "macos")]
+    pub fn add_arc(&mut self,
+                   center: CGPoint,
+                   radius: CGFloat,
+                   start_angle: CGFloat,
+                   end_angle: CGFloat,
+                   clockwise: bool) {
         unsafe {
-            CGPathGetTypeID()
+            CGPathAddArc(self.as_mut_ptr(),
+                         ptr::null(),
+                         center.x,
+                         center.y,
+                         radius,
+                         start_angle,
+                         end_angle,
+                         clockwise)
         }
     }
 
-    pub fn apply<'a, F>(&'a self, mut closure: &'a F) where F: FnMut(CGPathElementRef<'a>) {
+    /// Adds an arc of a circle, from `start_angle` to `end_angle` in radians.
+    ///
+    /// `clockwise` refers to a y-up coordinate system, i.e. clockwise arcs go from the start
+    /// angle towards smaller angles. If the path has a current point, a line is added from it to
+    /// the start of the arc; otherwise a new subpath is started there. The arc is approximated
+    /// by one cubic curve per quarter turn or part thereof.
+    #[cfg(not(target_os = "macos"))]
+    pub fn add_arc(&mut self,
+                   center: CGPoint,
+                   radius: CGFloat,
+                   start_angle: CGFloat,
+                   end_angle: CGFloat,
+                   clockwise: bool) {
+        let (start, curves) = arc_elements(center, radius, start_angle, end_angle, clockwise);
+        if self.current_point.is_some() {
+            self.line_to(start);
+        } else {
+            self.move_to(start);
+        }
+        for element in curves {
+            self.add_element(element);
+        }
+    }
+
+    /// Adds `rect` with rounded corners as a closed subpath, going counterclockwise (in a y-up
+    /// coordinate system) from the end of the bottom-left corner.
+    ///
+    /// Each corner is a quarter ellipse with radii `corner_width` and `corner_height`, which are
+    /// clamped to half the size of the rectangle. If either is zero, this is the same as
+    /// `add_rect`.
+    #[cfg(target_os = "macos")]
+    pub fn add_rounded_rect(&mut self,
+                            rect: CGRect,
+                            corner_width: CGFloat,
+                            corner_height: CGFloat) {
+        let rect = rect.standardize();
+        // Quartz asserts that the corners fit.
+        let corner_width = corner_width.max(0.0).min(rect.size.width * 0.5);
+        let corner_height = corner_height.max(0.0).min(rect.size.height * 0.5);
         unsafe {
-            CGPathApply(self.as_ptr(), &mut closure as *mut _ as *mut c_void, do_apply::<F>);
+            CGPathAddRoundedRect(self.as_mut_ptr(),
+                                 ptr::null(),
+                                 rect,
+                                 corner_width,
+                                 corner_height)
         }
+    }
 
-        unsafe extern "C" fn do_apply<'a, F>(info: *mut c_void, element: *const CGPathElement)
-                                             where F: FnMut(CGPathElementRef<'a>) {
-            let closure = info as *mut *mut F;
-            (**closure)(CGPathElementRef::new(element))
+    /// Adds `rect` with rounded corners as a closed subpath, going counterclockwise (in a y-up
+    /// coordinate system) from the end of the bottom-left corner.
+    ///
+    /// Each corner is a quarter ellipse with radii `corner_width` and `corner_height`, which are
+    /// clamped to half the size of the rectangle. If either is zero, this is the same as
+    /// `add_rect`.
+    #[cfg(not(target_os = "macos"))]
+    pub fn add_rounded_rect(&mut self,
+                            rect: CGRect,
+                            corner_width: CGFloat,
+                            corner_height: CGFloat) {
+        for element in rounded_rect_elements(rect, corner_width, corner_height) {
+            self.add_element(element);
+        }
+    }
+
+    /// Appends the elements of `path`, transformed by `transform` if given.
+    #[cfg(target_os = "macos")]
+    pub fn add_path(&mut self, path: &CGPathRef, transform: Option<&CGAffineTransform>) {
+        let transform = transform.map_or(ptr::null(), |t| t as *const _);
+        unsafe {
+            CGPathAddPath(self.as_mut_ptr(), transform, path.as_ptr())
+        }
+    }
+
+    /// Appends the elements of `path`, transformed by `transform` if given.
+    #[cfg(not(target_os = "macos"))]
+    pub fn add_path(&mut self, path: &CGPathRef, transform: Option<&CGAffineTransform>) {
+        for element in &path.elements {
+            self.add_element(transform.map_or(*element, |t| element.apply_transform(t)));
+        }
+    }
+
+    /// Appends a single element, with the same effect as the corresponding method call.
+    pub fn add_element(&mut self, element: PathElement) {
+        match element {
+            PathElement::MoveTo(p) => self.move_to(p),
+            PathElement::LineTo(p) => self.line_to(p),
+            PathElement::QuadTo(c, p) => self.quad_to(c, p),
+            PathElement::CubicTo(c1, c2, p) => self.curve_to(c1, c2, p),
+            PathElement::Close => self.close(),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    #[inline]
+    fn as_mut_ptr(&mut self) -> ::sys::CGPathRef {
+        self.path.as_ptr()
+    }
+
+    /// Appends a segment ending at `end`, first moving back to the start of the subpath if the
+    /// last one was closed.
+    #[cfg(not(target_os = "macos"))]
+    fn push_segment(&mut self, element: PathElement, end: CGPoint) {
+        if self.current_point.is_none() {
+            return;
+        }
+        if let Some(&PathElement::Close) = self.path.elements.last() {
+            let start = self.subpath_start;
+            self.path.elements.push(PathElement::MoveTo(start));
+        }
+        self.path.elements.push(element);
+        self.current_point = Some(end);
+    }
+}
+
+/// Accumulates the bounding box of a set of points.
+struct Bounds {
+    min: CGPoint,
+    max: CGPoint,
+}
+
+impl Bounds {
+    fn new() -> Bounds {
+        Bounds {
+            min: CGPoint::new(CGFloat::INFINITY, CGFloat::INFINITY),
+            max: CGPoint::new(CGFloat::NEG_INFINITY, CGFloat::NEG_INFINITY),
+        }
+    }
+
+    fn add(&mut self, point: CGPoint) {
+        self.min = CGPoint::new(self.min.x.min(point.x), self.min.y.min(point.y));
+        self.max = CGPoint::new(self.max.x.max(point.x), self.max.y.max(point.y));
+    }
+
+    fn to_rect(&self) -> CGRect {
+        if self.min.x > self.max.x {
+            return CG_RECT_NULL;
+        }
+        CGRect::new(&self.min, &CGSize::new(self.max.x - self.min.x, self.max.y - self.min.y))
+    }
+}
+
+/// Evaluates the quadratic Bézier curve from `p0` to `p1` with control point `c` at `t`.
+fn quad_point(p0: CGPoint, c: CGPoint, p1: CGPoint, t: CGFloat) -> CGPoint {
+    let u = 1.0 - t;
+    CGPoint::new(u * u * p0.x + 2.0 * u * t * c.x + t * t * p1.x,
+                 u * u * p0.y + 2.0 * u * t * c.y + t * t * p1.y)
+}
+
+/// Evaluates the cubic Bézier curve from `p0` to `p1` with control points `c1` and `c2` at `t`.
+fn cubic_point(p0: CGPoint, c1: CGPoint, c2: CGPoint, p1: CGPoint, t: CGFloat) -> CGPoint {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    CGPoint::new(a * p0.x + b * c1.x + c * c2.x + d * p1.x,
+                 a * p0.y + b * c1.y + c * c2.y + d * p1.y)
+}
+
+/// Returns the parameters strictly between 0 and 1 at which one coordinate of a cubic Bézier
+/// curve has a local extremum.
+fn cubic_extrema(p0: CGFloat, c1: CGFloat, c2: CGFloat, p1: CGFloat) -> Vec<CGFloat> {
+    // The derivative, divided by 3, is a t² + b t + c.
+    let a = -p0 + 3.0 * (c1 - c2) + p1;
+    let b = 2.0 * (p0 - 2.0 * c1 + c2);
+    let c = c1 - p0;
+
+    let mut roots = vec![];
+    if a.abs() < 1e-12 {
+        if b != 0.0 {
+            roots.push(-c / b);
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant >= 0.0 {
+            let sqrt = discriminant.sqrt();
+            roots.push((-b + sqrt) / (2.0 * a));
+            roots.push((-b - sqrt) / (2.0 * a));
+        }
+    }
+    roots.retain(|&t| t > 0.0 && t < 1.0);
+    roots
+}
+
+/// A drawn segment of a path, with its start point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Segment {
+    Line(CGPoint, CGPoint),
+    Quad(CGPoint, CGPoint, CGPoint),
+    Cubic(CGPoint, CGPoint, CGPoint, CGPoint),
+}
+
+/// A subpath as a list of segments, with `Close` turned into a line if it isn't degenerate.
+struct Subpath {
+    segments: Vec<Segment>,
+    closed: bool,
+}
+
+impl Segment {
+    fn start(&self) -> CGPoint {
+        match *self {
+            Segment::Line(p0, _) | Segment::Quad(p0, _, _) | Segment::Cubic(p0, _, _, _) => p0,
+        }
+    }
+
+    fn end(&self) -> CGPoint {
+        match *self {
+            Segment::Line(_, p1) | Segment::Quad(_, _, p1) | Segment::Cubic(_, _, _, p1) => p1,
+        }
+    }
+
+    /// Returns the element that continues a path with this segment.
+    fn to_element(self) -> PathElement {
+        match self {
+            Segment::Line(_, p1) => PathElement::LineTo(p1),
+            Segment::Quad(_, c, p1) => PathElement::QuadTo(c, p1),
+            Segment::Cubic(_, c1, c2, p1) => PathElement::CubicTo(c1, c2, p1),
+        }
+    }
+
+    fn point_at(&self, t: CGFloat) -> CGPoint {
+        match *self {
+            Segment::Line(p0, p1) => lerp(p0, p1, t),
+            Segment::Quad(p0, c, p1) => quad_point(p0, c, p1, t),
+            Segment::Cubic(p0, c1, c2, p1) => cubic_point(p0, c1, c2, p1, t),
+        }
+    }
+
+    fn derivative_at(&self, t: CGFloat) -> CGPoint {
+        match *self {
+            Segment::Line(p0, p1) => sub(p1, p0),
+            Segment::Quad(p0, c, p1) => scale(lerp(sub(c, p0), sub(p1, c), t), 2.0),
+            Segment::Cubic(p0, c1, c2, p1) => {
+                let (d0, d1, d2) = (sub(c1, p0), sub(c2, c1), sub(p1, c2));
+                scale(lerp(lerp(d0, d1, t), lerp(d1, d2, t), t), 3.0)
+            }
+        }
+    }
+
+    /// Returns the arc length between the parameters `t0` and `t1`, to within about
+    /// `tolerance`.
+    fn arc_length(&self, t0: CGFloat, t1: CGFloat, tolerance: CGFloat) -> CGFloat {
+        if let Segment::Line(p0, p1) = *self {
+            let d = sub(p1, p0);
+            return distance(d.x, d.y) * (t1 - t0);
+        }
+        self.refine_arc_length(t0, t1, self.quadrature(t0, t1), tolerance, 0)
+    }
+
+    /// Splits `t0..t1` in halves until their lengths add up to the length of the whole.
+    fn refine_arc_length(&self,
+                         t0: CGFloat,
+                         t1: CGFloat,
+                         whole: CGFloat,
+                         tolerance: CGFloat,
+                         depth: u32)
+                         -> CGFloat {
+        const MAX_DEPTH: u32 = 12;
+
+        let mid = (t0 + t1) * 0.5;
+        let (left, right) = (self.quadrature(t0, mid), self.quadrature(mid, t1));
+        if depth == MAX_DEPTH || (left + right - whole).abs() <= tolerance {
+            return left + right;
+        }
+        self.refine_arc_length(t0, mid, left, tolerance * 0.5, depth + 1) +
+            self.refine_arc_length(mid, t1, right, tolerance * 0.5, depth + 1)
+    }
+
+    /// Estimates the arc length between `t0` and `t1` by five-point Gauss-Legendre quadrature of
+    /// the speed.
+    fn quadrature(&self, t0: CGFloat, t1: CGFloat) -> CGFloat {
+        const NODES: [(CGFloat, CGFloat); 5] = [
+            (0.0, 0.568_888_888_888_888_9),
+            (-0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+            (0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+            (-0.906_179_845_938_664, 0.236_926_885_056_189_1),
+            (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+        ];
+
+        let (middle, half) = ((t0 + t1) * 0.5, (t1 - t0) * 0.5);
+        NODES.iter().map(|&(node, weight)| {
+            let d = self.derivative_at(middle + node * half);
+            weight * distance(d.x, d.y) * half
+        }).sum()
+    }
+
+    #[inline]
+    fn length(&self, tolerance: CGFloat) -> CGFloat {
+        self.arc_length(0.0, 1.0, tolerance)
+    }
+
+    /// Returns the parameter at which the arc length from the start of the segment is `length`,
+    /// given the total length of the segment.
+    fn t_at_length(&self, length: CGFloat, total: CGFloat, tolerance: CGFloat) -> CGFloat {
+        if length <= 0.0 || total <= 0.0 {
+            return 0.0;
+        }
+        if length >= total {
+            return 1.0;
+        }
+        if let Segment::Line(..) = *self {
+            return length / total;
+        }
+
+        // Bisect, measuring only the part between the last two guesses each time.
+        let (mut low, mut high, mut low_length) = (0.0, 1.0, 0.0);
+        for _ in 0..48 {
+            let mid = (low + high) * 0.5;
+            let mid_length = low_length + self.arc_length(low, mid, tolerance);
+            if (mid_length - length).abs() <= tolerance * 1e-3 {
+                return mid;
+            }
+            if mid_length < length {
+                low = mid;
+                low_length = mid_length;
+            } else {
+                high = mid;
+            }
+        }
+        (low + high) * 0.5
+    }
+
+    /// Returns the unit direction of the segment at `t`.
+    fn tangent_at(&self, t: CGFloat) -> CGPoint {
+        let d = self.derivative_at(t);
+        let length = distance(d.x, d.y);
+        if length > 1e-12 {
+            return scale(d, 1.0 / length);
+        }
+        // At a cusp or a control point coinciding with an end point, use the nearby direction.
+        let before = self.point_at((t - 1e-4).max(0.0));
+        let after = self.point_at((t + 1e-4).min(1.0));
+        if before == after {
+            let chord = sub(self.end(), self.start());
+            return scale(chord, 1.0 / distance(chord.x, chord.y));
+        }
+        direction(before, after)
+    }
+
+    /// Returns the part of the segment between the parameters `t0` and `t1`.
+    fn sub_segment(&self, t0: CGFloat, t1: CGFloat) -> Segment {
+        let head = if t1 < 1.0 { self.split(t1).0 } else { *self };
+        if t0 <= 0.0 || t1 <= 0.0 {
+            return head;
+        }
+        head.split(t0 / t1).1
+    }
+
+    /// Splits the segment at `t` with de Casteljau's algorithm.
+    fn split(&self, t: CGFloat) -> (Segment, Segment) {
+        match *self {
+            Segment::Line(p0, p1) => {
+                let mid = lerp(p0, p1, t);
+                (Segment::Line(p0, mid), Segment::Line(mid, p1))
+            }
+            Segment::Quad(p0, c, p1) => {
+                let (a, b) = (lerp(p0, c, t), lerp(c, p1, t));
+                let mid = lerp(a, b, t);
+                (Segment::Quad(p0, a, mid), Segment::Quad(mid, b, p1))
+            }
+            Segment::Cubic(p0, c1, c2, p1) => {
+                let (a, b, c) = (lerp(p0, c1, t), lerp(c1, c2, t), lerp(c2, p1, t));
+                let (d, e) = (lerp(a, b, t), lerp(b, c, t));
+                let mid = lerp(d, e, t);
+                (Segment::Cubic(p0, a, d, mid), Segment::Cubic(mid, e, c, p1))
+            }
+        }
+    }
+}
+
+/// Splits `elements` into subpaths of segments. Subpaths without segments are omitted.
+fn subpaths(elements: &[PathElement]) -> Vec<Subpath> {
+    let mut subpaths = vec![];
+    let mut current = Subpath {
+        segments: vec![],
+        closed: false,
+    };
+    let mut start = CGPoint::new(0.0, 0.0);
+    let mut last = start;
+    for &element in elements {
+        let segment = match element {
+            PathElement::MoveTo(p) => {
+                finish_subpath(&mut subpaths, &mut current);
+                start = p;
+                last = p;
+                continue;
+            }
+            PathElement::Close => {
+                if last != start {
+                    current.segments.push(Segment::Line(last, start));
+                }
+                current.closed = true;
+                last = start;
+                continue;
+            }
+            PathElement::LineTo(p) => Segment::Line(last, p),
+            PathElement::QuadTo(c, p) => Segment::Quad(last, c, p),
+            PathElement::CubicTo(c1, c2, p) => Segment::Cubic(last, c1, c2, p),
+        };
+        if current.closed {
+            finish_subpath(&mut subpaths, &mut current);
+        }
+        current.segments.push(segment);
+        last = segment.end();
+    }
+    finish_subpath(&mut subpaths, &mut current);
+    subpaths
+}
+
+fn finish_subpath(subpaths: &mut Vec<Subpath>, current: &mut Subpath) {
+    let segments = current.segments.split_off(0);
+    if !segments.is_empty() {
+        subpaths.push(Subpath {
+            segments: segments,
+            closed: current.closed,
+        });
+    }
+    current.closed = false;
+}
+
+#[inline]
+fn lerp(a: CGPoint, b: CGPoint, t: CGFloat) -> CGPoint {
+    CGPoint::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+}
+
+/// Returns the elements of `rect` as a closed subpath, as added by `CGMutablePath::add_rect`.
+#[cfg(any(test, not(target_os = "macos")))]
+fn rect_elements(rect: CGRect) -> Vec<PathElement> {
+    let (x, y) = (rect.origin.x, rect.origin.y);
+    let (width, height) = (rect.size.width, rect.size.height);
+    vec![
+        PathElement::MoveTo(CGPoint::new(x, y)),
+        PathElement::LineTo(CGPoint::new(x + width, y)),
+        PathElement::LineTo(CGPoint::new(x + width, y + height)),
+        PathElement::LineTo(CGPoint::new(x, y + height)),
+        PathElement::Close,
+    ]
+}
+
+/// Returns the elements of the ellipse inscribed in `rect`, as added by
+/// `CGMutablePath::add_ellipse`.
+#[cfg(any(test, not(target_os = "macos")))]
+fn ellipse_elements(rect: CGRect) -> Vec<PathElement> {
+    let rect = rect.standardize();
+    let (rx, ry) = (rect.size.width * 0.5, rect.size.height * 0.5);
+    let (cx, cy) = (rect.origin.x + rx, rect.origin.y + ry);
+    let (kx, ky) = (rx * QUARTER_CIRCLE_KAPPA, ry * QUARTER_CIRCLE_KAPPA);
+    vec![
+        PathElement::MoveTo(CGPoint::new(cx + rx, cy)),
+        PathElement::CubicTo(CGPoint::new(cx + rx, cy + ky),
+                             CGPoint::new(cx + kx, cy + ry),
+                             CGPoint::new(cx, cy + ry)),
+        PathElement::CubicTo(CGPoint::new(cx - kx, cy + ry),
+                             CGPoint::new(cx - rx, cy + ky),
+                             CGPoint::new(cx - rx, cy)),
+        PathElement::CubicTo(CGPoint::new(cx - rx, cy - ky),
+                             CGPoint::new(cx - kx, cy - ry),
+                             CGPoint::new(cx, cy - ry)),
+        PathElement::CubicTo(CGPoint::new(cx + kx, cy - ry),
+                             CGPoint::new(cx + rx, cy - ky),
+                             CGPoint::new(cx + rx, cy)),
+        PathElement::Close,
+    ]
+}
+
+/// Returns the start of an arc and the curves approximating it, as added by
+/// `CGMutablePath::add_arc` after the line or move to the start.
+#[cfg(any(test, not(target_os = "macos")))]
+fn arc_elements(center: CGPoint,
+                radius: CGFloat,
+                start_angle: CGFloat,
+                end_angle: CGFloat,
+                clockwise: bool)
+                -> (CGPoint, Vec<PathElement>) {
+    let point_at = |angle: CGFloat| {
+        CGPoint::new(center.x + radius * angle.cos(), center.y + radius * angle.sin())
+    };
+
+    let sweep = arc_sweep(start_angle, end_angle, clockwise);
+    let segments = (sweep.abs() / (PI * 0.5) - 1e-9).ceil().max(0.0) as usize;
+    let step = sweep / segments.max(1) as CGFloat;
+    // The control points of a cubic approximating an arc of angle `step` lie on the tangents at
+    // its ends, at this distance from them.
+    let k = radius * 4.0 / 3.0 * (step / 4.0).tan();
+    let curves = (0..segments).map(|i| {
+        let a0 = start_angle + step * i as CGFloat;
+        let a1 = a0 + step;
+        let (p0, p1) = (point_at(a0), point_at(a1));
+        PathElement::CubicTo(CGPoint::new(p0.x - k * a0.sin(), p0.y + k * a0.cos()),
+                             CGPoint::new(p1.x + k * a1.sin(), p1.y - k * a1.cos()),
+                             p1)
+    }).collect();
+    (point_at(start_angle), curves)
+}
+
+/// Returns the elements of `rect` with rounded corners, as added by
+/// `CGMutablePath::add_rounded_rect`.
+#[cfg(any(test, not(target_os = "macos")))]
+fn rounded_rect_elements(rect: CGRect,
+                         corner_width: CGFloat,
+                         corner_height: CGFloat)
+                         -> Vec<PathElement> {
+    let rect = rect.standardize();
+    let rx = corner_width.max(0.0).min(rect.size.width * 0.5);
+    let ry = corner_height.max(0.0).min(rect.size.height * 0.5);
+    if rx == 0.0 || ry == 0.0 {
+        return rect_elements(rect);
+    }
+
+    let (min_x, min_y) = (rect.origin.x, rect.origin.y);
+    let (max_x, max_y) = (min_x + rect.size.width, min_y + rect.size.height);
+    let (kx, ky) = (rx * QUARTER_CIRCLE_KAPPA, ry * QUARTER_CIRCLE_KAPPA);
+    vec![
+        PathElement::MoveTo(CGPoint::new(min_x + rx, min_y)),
+        PathElement::LineTo(CGPoint::new(max_x - rx, min_y)),
+        PathElement::CubicTo(CGPoint::new(max_x - rx + kx, min_y),
+                             CGPoint::new(max_x, min_y + ry - ky),
+                             CGPoint::new(max_x, min_y + ry)),
+        PathElement::LineTo(CGPoint::new(max_x, max_y - ry)),
+        PathElement::CubicTo(CGPoint::new(max_x, max_y - ry + ky),
+                             CGPoint::new(max_x - rx + kx, max_y),
+                             CGPoint::new(max_x - rx, max_y)),
+        PathElement::LineTo(CGPoint::new(min_x + rx, max_y)),
+        PathElement::CubicTo(CGPoint::new(min_x + rx - kx, max_y),
+                             CGPoint::new(min_x, max_y - ry + ky),
+                             CGPoint::new(min_x, max_y - ry)),
+        PathElement::LineTo(CGPoint::new(min_x, min_y + ry)),
+        PathElement::CubicTo(CGPoint::new(min_x, min_y + ry - ky),
+                             CGPoint::new(min_x + rx - kx, min_y),
+                             CGPoint::new(min_x + rx, min_y)),
+        PathElement::Close,
+    ]
+}
+
+/// Returns the signed angle swept by an arc from `start_angle` to `end_angle` in the given
+/// direction, which is negative for clockwise arcs and at most a full turn.
+#[cfg(any(test, not(target_os = "macos")))]
+fn arc_sweep(start_angle: CGFloat, end_angle: CGFloat, clockwise: bool) -> CGFloat {
+    let full_turn = 2.0 * PI;
+    let sweep = end_angle - start_angle;
+    if clockwise {
+        if sweep > 0.0 {
+            sweep % full_turn - full_turn
+        } else {
+            sweep.max(-full_turn)
         }
+    } else if sweep < 0.0 {
+        sweep % full_turn + full_turn
+    } else {
+        sweep.min(full_turn)
     }
 }
 
@@ -55,11 +1812,13 @@ pub enum CGPathElementType {
     CloseSubpath = 4,
 }
 
+#[cfg(target_os = "macos")]
 pub struct CGPathElementRef<'a> {
     element: *const CGPathElement,
     phantom: PhantomData<&'a CGPathElement>,
 }
 
+#[cfg(target_os = "macos")]
 impl<'a> CGPathElementRef<'a> {
     fn new<'b>(element: *const CGPathElement) -> CGPathElementRef<'b> {
         CGPathElementRef {
@@ -69,6 +1828,7 @@ impl<'a> CGPathElementRef<'a> {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl<'a> Deref for CGPathElementRef<'a> {
     type Target = CGPathElement;
     fn deref(&self) -> &CGPathElement {
@@ -105,11 +1865,670 @@ impl CGPathElement {
     }
 }
 
+#[test]
+fn mutable_path_elements() {
+    use geometry::CGSize;
+
+    let mut path = CGMutablePath::new();
+    path.line_to(CGPoint::new(1.0, 1.0));
+    assert!(path.current_point().is_none());
+
+    path.move_to(CGPoint::new(0.0, 0.0));
+    path.line_to(CGPoint::new(10.0, 0.0));
+    path.quad_to(CGPoint::new(10.0, 10.0), CGPoint::new(0.0, 10.0));
+    path.close();
+    path.curve_to(CGPoint::new(1.0, 2.0), CGPoint::new(3.0, 4.0), CGPoint::new(5.0, 6.0));
+    assert_eq!(path.current_point(), Some(CGPoint::new(5.0, 6.0)));
+    assert_eq!(path.elements(), vec![
+        PathElement::MoveTo(CGPoint::new(0.0, 0.0)),
+        PathElement::LineTo(CGPoint::new(10.0, 0.0)),
+        PathElement::QuadTo(CGPoint::new(10.0, 10.0), CGPoint::new(0.0, 10.0)),
+        PathElement::Close,
+        PathElement::MoveTo(CGPoint::new(0.0, 0.0)),
+        PathElement::CubicTo(CGPoint::new(1.0, 2.0),
+                             CGPoint::new(3.0, 4.0),
+                             CGPoint::new(5.0, 6.0)),
+    ]);
+
+    let mut rect = CGMutablePath::new();
+    rect.add_rect(CGRect::new(&CGPoint::new(1.0, 2.0), &CGSize::new(3.0, 4.0)));
+    assert_eq!(rect.elements(), vec![
+        PathElement::MoveTo(CGPoint::new(1.0, 2.0)),
+        PathElement::LineTo(CGPoint::new(4.0, 2.0)),
+        PathElement::LineTo(CGPoint::new(4.0, 6.0)),
+        PathElement::LineTo(CGPoint::new(1.0, 6.0)),
+        PathElement::Close,
+    ]);
+
+    let mut copy = CGMutablePath::new();
+    copy.add_path(&rect, Some(&CGAffineTransform::make_translation(1.0, 0.0)));
+    let elements = copy.into_path().elements();
+    assert_eq!(elements.len(), 5);
+    assert_eq!(elements[2], PathElement::LineTo(CGPoint::new(5.0, 6.0)));
+}
+
+#[test]
+fn mutable_path_curves() {
+    use geometry::{ApproxEq, CGSize};
+
+    let end_point = |elements: &[PathElement]| match elements[elements.len() - 1] {
+        PathElement::CubicTo(_, _, p) => p,
+        ref element => panic!("unexpected {:?}", element),
+    };
+
+    let mut ellipse = CGMutablePath::new();
+    ellipse.add_ellipse(CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(4.0, 2.0)));
+    let elements = ellipse.elements();
+    assert_eq!(elements.len(), 6);
+    assert_eq!(elements[0], PathElement::MoveTo(CGPoint::new(4.0, 1.0)));
+    assert_eq!(end_point(&elements[..2]), CGPoint::new(2.0, 2.0));
+    assert_eq!(elements[5], PathElement::Close);
+
+    // A counterclockwise half circle takes two quarter curves, starting with a line from the
+    // current point.
+    let mut arc = CGMutablePath::new();
+    arc.move_to(CGPoint::new(0.0, 0.0));
+    arc.add_arc(CGPoint::new(0.0, 0.0), 2.0, 0.0, PI, false);
+    let elements = arc.elements();
+    assert_eq!(elements.len(), 4);
+    assert_eq!(elements[1], PathElement::LineTo(CGPoint::new(2.0, 0.0)));
+    assert!(end_point(&elements[..3]).approx_eq(&CGPoint::new(0.0, 2.0)));
+    assert!(end_point(&elements).approx_eq(&CGPoint::new(-2.0, 0.0)));
+
+    // Going clockwise from 0 to π/2 takes the long way around.
+    let mut arc = CGMutablePath::new();
+    arc.add_arc(CGPoint::new(0.0, 0.0), 1.0, 0.0, PI * 0.5, true);
+    let elements = arc.elements();
+    assert_eq!(elements.len(), 4);
+    assert!(end_point(&elements[..2]).approx_eq(&CGPoint::new(0.0, -1.0)));
+    assert!(end_point(&elements).approx_eq(&CGPoint::new(0.0, 1.0)));
+
+    let mut rounded = CGMutablePath::new();
+    rounded.add_rounded_rect(CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(10.0, 4.0)),
+                             3.0,
+                             3.0);
+    let elements = rounded.elements();
+    assert_eq!(elements.len(), 10);
+    assert_eq!(elements[0], PathElement::MoveTo(CGPoint::new(3.0, 0.0)));
+    assert_eq!(end_point(&elements[..3]), CGPoint::new(10.0, 2.0));
+}
+
+#[test]
+fn path_element_traversal() {
+    let elements = vec![
+        PathElement::MoveTo(CGPoint::new(0.0, 0.0)),
+        PathElement::QuadTo(CGPoint::new(1.0, 2.0), CGPoint::new(2.0, 0.0)),
+        PathElement::CubicTo(CGPoint::new(3.0, 1.0),
+                             CGPoint::new(4.0, -1.0),
+                             CGPoint::new(5.0, 0.0)),
+        PathElement::Close,
+    ];
+    let path = CGPath::from_elements(elements.clone());
+    assert_eq!(path.elements(), elements);
+    assert_eq!(path.elements().last().unwrap().end_point(), None);
+    assert_eq!(elements[2].end_point(), Some(CGPoint::new(5.0, 0.0)));
+
+    let t = CGAffineTransform::make_scale(2.0, -1.0);
+    let scaled = CGPath::from_elements(path.elements().iter().map(|e| e.apply_transform(&t)));
+    assert_eq!(scaled.elements()[1],
+               PathElement::QuadTo(CGPoint::new(2.0, -2.0), CGPoint::new(4.0, 0.0)));
+
+    let mut added = CGMutablePath::new();
+    added.add_path(&path, Some(&t));
+    assert_eq!(added.elements(), scaled.elements());
+}
+
+#[test]
+fn path_bounds() {
+    use geometry::ApproxEq;
+
+    let empty = CGMutablePath::new();
+    assert!(empty.bounding_box().is_null());
+    assert!(empty.path_bounding_box().is_null());
+
+    let mut path = CGMutablePath::new();
+    path.move_to(CGPoint::new(0.0, 0.0));
+    path.quad_to(CGPoint::new(1.0, 2.0), CGPoint::new(2.0, 0.0));
+    path.curve_to(CGPoint::new(2.0, -3.0), CGPoint::new(6.0, -3.0), CGPoint::new(6.0, 0.0));
+    path.close();
+
+    assert_eq!(path.bounding_box(),
+               CGRect::new(&CGPoint::new(0.0, -3.0), &CGSize::new(6.0, 5.0)));
+    // The quad peaks at y = 1 and the cubic bottoms out at y = -2.25.
+    assert!(path.path_bounding_box().approx_eq(&CGRect::new(&CGPoint::new(0.0, -2.25),
+                                                            &CGSize::new(6.0, 3.25))));
+
+    let mut circle = CGMutablePath::new();
+    circle.add_ellipse(CGRect::new(&CGPoint::new(-1.0, -1.0), &CGSize::new(2.0, 2.0)));
+    assert!(circle.path_bounding_box().approx_eq(&CGRect::new(&CGPoint::new(-1.0, -1.0),
+                                                              &CGSize::new(2.0, 2.0))));
+}
+
+#[test]
+fn path_flattening() {
+    let mut path = CGMutablePath::new();
+    path.move_to(CGPoint::new(5.0, 5.0));
+    path.add_rect(CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(2.0, 1.0)));
+    path.line_to(CGPoint::new(3.0, 3.0));
+    path.move_to(CGPoint::new(0.0, 0.0));
+    path.quad_to(CGPoint::new(50.0, 100.0), CGPoint::new(100.0, 0.0));
+
+    let polylines = path.flatten(0.1);
+    assert_eq!(polylines.len(), 3);
+    assert_eq!(polylines[0], Polyline {
+        points: vec![
+            CGPoint::new(0.0, 0.0),
+            CGPoint::new(2.0, 0.0),
+            CGPoint::new(2.0, 1.0),
+            CGPoint::new(0.0, 1.0),
+        ],
+        closed: true,
+    });
+    assert_eq!(polylines[1], Polyline {
+        points: vec![CGPoint::new(0.0, 0.0), CGPoint::new(3.0, 3.0)],
+        closed: false,
+    });
+
+    // The chords must stay within the tolerance of the curve, which peaks at y = 50.
+    let curve = &polylines[2];
+    assert!(!curve.closed);
+    assert_eq!(curve.points[curve.points.len() - 1], CGPoint::new(100.0, 0.0));
+    let steps = curve.points.len() - 1;
+    assert_eq!(steps, flattening_steps(50.0, 0.1));
+    assert_eq!(path.flatten(0.4)[2].points.len() - 1, steps.div_ceil(2));
+    for pair in curve.points.windows(2) {
+        let mid_x = (pair[0].x + pair[1].x) * 0.5;
+        let t = mid_x / 100.0;
+        let on_curve = 200.0 * t * (1.0 - t);
+        assert!((on_curve - (pair[0].y + pair[1].y) * 0.5).abs() <= 0.1);
+    }
+
+    let mut circle = CGMutablePath::new();
+    circle.add_ellipse(CGRect::new(&CGPoint::new(-10.0, -10.0), &CGSize::new(20.0, 20.0)));
+    let polylines = circle.flatten(0.01);
+    assert_eq!(polylines.len(), 1);
+    assert!(polylines[0].closed);
+    for point in &polylines[0].points {
+        assert!((distance(point.x, point.y) - 10.0).abs() < 0.01);
+    }
+    assert_eq!(polylines, circle.flatten(0.01));
+
+    // A curve drawing back to the start of a closed subpath doesn't repeat it.
+    let mut loop_path = CGMutablePath::new();
+    loop_path.move_to(CGPoint::new(0.0, 0.0));
+    loop_path.line_to(CGPoint::new(10.0, 0.0));
+    loop_path.quad_to(CGPoint::new(10.0, 10.0), CGPoint::new(0.0, 0.0));
+    loop_path.close();
+    let polylines = loop_path.flatten(0.1);
+    assert_eq!(polylines.len(), 1);
+    let points = &polylines[0].points;
+    assert_eq!(points[0], CGPoint::new(0.0, 0.0));
+    assert!(points[points.len() - 1] != points[0]);
+    assert!(polylines[0].closed);
+}
+
+#[test]
+#[should_panic]
+fn path_flattening_rejects_negative_tolerance() {
+    let mut path = CGMutablePath::new();
+    path.move_to(CGPoint::new(0.0, 0.0));
+    path.quad_to(CGPoint::new(50.0, 100.0), CGPoint::new(100.0, 0.0));
+    path.flatten(-0.1);
+}
+
+#[test]
+fn path_contains_point() {
+    let rect = |x, y, width, height| CGRect::new(&CGPoint::new(x, y), &CGSize::new(width, height));
+
+    // Two nested squares with the same orientation, and a third drawn the other way around.
+    let mut path = CGMutablePath::new();
+    path.add_rect(rect(0.0, 0.0, 10.0, 10.0));
+    path.add_rect(rect(2.0, 2.0, 6.0, 6.0));
+    path.move_to(CGPoint::new(20.0, 0.0));
+    path.line_to(CGPoint::new(20.0, 10.0));
+    path.line_to(CGPoint::new(30.0, 10.0));
+    path.line_to(CGPoint::new(30.0, 0.0));
+
+    let inside = |x, y, rule| path.contains_point(CGPoint::new(x, y), None, rule);
+    assert!(inside(1.0, 5.0, CGPathFillRule::Winding));
+    assert!(inside(1.0, 5.0, CGPathFillRule::EvenOdd));
+    assert!(inside(5.0, 5.0, CGPathFillRule::Winding));
+    assert!(!inside(5.0, 5.0, CGPathFillRule::EvenOdd));
+    // The open third subpath is closed implicitly.
+    assert!(inside(25.0, 5.0, CGPathFillRule::Winding));
+    assert!(!inside(15.0, 5.0, CGPathFillRule::Winding));
+    assert!(!inside(5.0, 11.0, CGPathFillRule::EvenOdd));
+
+    let scale = CGAffineTransform::make_scale(2.0, 2.0);
+    assert!(path.contains_point(CGPoint::new(19.0, 10.0), Some(&scale), CGPathFillRule::Winding));
+    assert!(!path.contains_point(CGPoint::new(1.0, 5.0),
+                                 Some(&CGAffineTransform::make_scale(0.0, 1.0)),
+                                 CGPathFillRule::Winding));
+
+    // Points just inside and outside the curved parts of a circle.
+    let mut circle = CGMutablePath::new();
+    circle.add_arc(CGPoint::new(0.0, 0.0), 10.0, 0.0, 2.0 * PI, false);
+    circle.close();
+    for i in 0..16 {
+        let angle = i as CGFloat * PI / 8.0 + 0.1;
+        let at = |r: CGFloat| CGPoint::new(r * angle.cos(), r * angle.sin());
+        assert!(circle.contains_point(at(9.9), None, CGPathFillRule::Winding));
+        assert!(!circle.contains_point(at(10.1), None, CGPathFillRule::Winding));
+    }
+
+    let mut quad = CGMutablePath::new();
+    quad.move_to(CGPoint::new(0.0, 0.0));
+    quad.quad_to(CGPoint::new(5.0, 10.0), CGPoint::new(10.0, 0.0));
+    assert!(quad.contains_point(CGPoint::new(5.0, 4.9), None, CGPathFillRule::EvenOdd));
+    assert!(!quad.contains_point(CGPoint::new(5.0, 5.1), None, CGPathFillRule::EvenOdd));
+}
+
+#[test]
+fn path_stroke_outline() {
+    let inside = |path: &CGPath, x, y| {
+        path.contains_point(CGPoint::new(x, y), None, CGPathFillRule::Winding)
+    };
+
+    // An L shape with its corner at (10, 0).
+    let mut l = CGMutablePath::new();
+    l.move_to(CGPoint::new(0.0, 0.0));
+    l.line_to(CGPoint::new(10.0, 0.0));
+    l.line_to(CGPoint::new(10.0, 10.0));
+
+    let butt = l.approximate_stroke(2.0, CGLineCap::Butt, CGLineJoin::Miter, 10.0);
+    assert!(inside(&butt, 5.0, 0.9));
+    assert!(inside(&butt, 5.0, -0.9));
+    assert!(!inside(&butt, 5.0, 1.1));
+    assert!(!inside(&butt, -0.1, 0.0));
+    assert!(!inside(&butt, 10.0, 10.1));
+    // The mitered corner is square.
+    assert!(inside(&butt, 10.9, -0.9));
+
+    let bevel = l.approximate_stroke(2.0, CGLineCap::Square, CGLineJoin::Bevel, 10.0);
+    assert!(!inside(&bevel, 10.9, -0.9));
+    assert!(inside(&bevel, 10.4, -0.4));
+    assert!(inside(&bevel, -0.9, 0.9));
+    assert!(inside(&bevel, 10.0, 10.9));
+    // A right angle needs a miter limit of at least √2.
+    let limited = l.approximate_stroke(2.0, CGLineCap::Butt, CGLineJoin::Miter, 1.4);
+    assert_eq!(limited.elements(), bevel.elements()[..limited.elements().len()].to_vec());
+
+    let round = l.approximate_stroke(2.0, CGLineCap::Round, CGLineJoin::Round, 10.0);
+    assert!(inside(&round, -0.7, 0.7));
+    assert!(!inside(&round, -0.8, 0.8));
+    assert!(inside(&round, 10.7, -0.7));
+    assert!(!inside(&round, 10.8, -0.8));
+
+    // A closed square stroked from the inside and outside leaves the middle unfilled.
+    let mut square = CGMutablePath::new();
+    square.add_rect(CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(10.0, 10.0)));
+    let outline = square.approximate_stroke(1.0, CGLineCap::Butt, CGLineJoin::Miter, 10.0);
+    assert!(inside(&outline, -0.4, -0.4));
+    assert!(inside(&outline, 0.4, 5.0));
+    assert!(!inside(&outline, 5.0, 5.0));
+
+    // A closed subpath of two points is joined at both ends instead of capped.
+    let mut there_and_back = CGMutablePath::new();
+    there_and_back.move_to(CGPoint::new(0.0, 0.0));
+    there_and_back.line_to(CGPoint::new(10.0, 0.0));
+    there_and_back.close();
+    let joined = there_and_back.approximate_stroke(2.0, CGLineCap::Square, CGLineJoin::Bevel, 10.0);
+    assert!(inside(&joined, 0.1, 0.9));
+    assert!(!inside(&joined, -0.1, 0.0));
+    assert!(!inside(&joined, 10.1, 0.0));
+    let joined = there_and_back.approximate_stroke(2.0, CGLineCap::Butt, CGLineJoin::Round, 10.0);
+    assert!(inside(&joined, -0.9, 0.0));
+
+    // A dot is only visible with round or square caps.
+    let mut dot = CGMutablePath::new();
+    dot.move_to(CGPoint::new(3.0, 3.0));
+    dot.line_to(CGPoint::new(3.0, 3.0));
+    let dot_stroke = |cap| dot.approximate_stroke(2.0, cap, CGLineJoin::Miter, 10.0);
+    assert!(dot_stroke(CGLineCap::Butt).elements().is_empty());
+    assert!(inside(&dot_stroke(CGLineCap::Square), 3.9, 3.9));
+    assert!(!inside(&dot_stroke(CGLineCap::Round), 3.9, 3.9));
+    let empty = l.approximate_stroke(0.0, CGLineCap::Round, CGLineJoin::Round, 10.0);
+    assert!(empty.elements().is_empty());
+
+    // Curves are followed closely.
+    let mut circle = CGMutablePath::new();
+    circle.add_ellipse(CGRect::new(&CGPoint::new(-10.0, -10.0), &CGSize::new(20.0, 20.0)));
+    let ring = circle.approximate_stroke(2.0, CGLineCap::Butt, CGLineJoin::Miter, 10.0);
+    for i in 0..12 {
+        let angle = i as CGFloat * PI / 6.0 + 0.05;
+        let at = |r: CGFloat| (r * angle.cos(), r * angle.sin());
+        for &(r, expected) in &[(8.9, false), (9.1, true), (10.9, true), (11.1, false)] {
+            let (x, y) = at(r);
+            assert_eq!(inside(&ring, x, y), expected);
+        }
+    }
+}
+
+#[test]
+fn path_dashing() {
+    use geometry::ApproxEq;
+
+    let mut line = CGMutablePath::new();
+    line.move_to(CGPoint::new(0.0, 0.0));
+    line.line_to(CGPoint::new(10.0, 0.0));
+    line.line_to(CGPoint::new(10.0, 3.0));
+
+    let dashed = line.dash(1.0, &[4.0, 2.0]);
+    let expected = vec![
+        PathElement::MoveTo(CGPoint::new(0.0, 0.0)),
+        PathElement::LineTo(CGPoint::new(3.0, 0.0)),
+        PathElement::MoveTo(CGPoint::new(5.0, 0.0)),
+        PathElement::LineTo(CGPoint::new(9.0, 0.0)),
+        PathElement::MoveTo(CGPoint::new(10.0, 1.0)),
+        PathElement::LineTo(CGPoint::new(10.0, 3.0)),
+    ];
+    assert_eq!(dashed.elements(), expected);
+    // A phase of a whole pattern, or a negative one, is equivalent.
+    assert_eq!(line.dash(7.0, &[4.0, 2.0]).elements(), expected);
+    assert_eq!(line.dash(-5.0, &[4.0, 2.0]).elements(), expected);
+
+    // A dash continues around a corner.
+    let corner = line.dash(0.0, &[11.0, 1.0]).elements();
+    assert_eq!(corner[..3].to_vec(), vec![
+        PathElement::MoveTo(CGPoint::new(0.0, 0.0)),
+        PathElement::LineTo(CGPoint::new(10.0, 0.0)),
+        PathElement::LineTo(CGPoint::new(10.0, 1.0)),
+    ]);
+
+    assert_eq!(line.dash(0.0, &[]).elements(), line.elements());
+    assert_eq!(line.dash(0.0, &[1.0, -1.0]).elements(), line.elements());
+
+    // Zero-length dashes give dots, and closed subpaths are dashed along the closing line.
+    let mut square = CGMutablePath::new();
+    square.add_rect(CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(2.0, 2.0)));
+    let dots = square.dash(0.0, &[0.0, 2.0]).elements();
+    assert_eq!(dots.len(), 8);
+    assert_eq!(dots[6], PathElement::MoveTo(CGPoint::new(0.0, 2.0)));
+    assert_eq!(dots[7], PathElement::LineTo(CGPoint::new(0.0, 2.0)));
+
+    // Dashes along a curve are pieces of the same curve, evenly spaced by arc length.
+    let mut circle = CGMutablePath::new();
+    circle.add_ellipse(CGRect::new(&CGPoint::new(-10.0, -10.0), &CGSize::new(20.0, 20.0)));
+    let length: CGFloat = subpaths(&circle.elements())[0].segments.iter()
+                                                           .map(|segment| segment.length(1e-9))
+                                                           .sum();
+    assert!((length - 2.0 * PI * 10.0).abs() < 0.1);
+    let dash = length / 16.0;
+    let dashed = circle.dash(0.0, &[dash, dash]);
+    let starts: Vec<CGPoint> = dashed.elements().iter().filter_map(|element| match *element {
+        PathElement::MoveTo(p) => Some(p),
+        _ => None,
+    }).collect();
+    assert_eq!(starts.len(), 8);
+    for (i, start) in starts.iter().enumerate() {
+        let angle = i as CGFloat * PI / 4.0;
+        let expected = CGPoint::new(10.0 * angle.cos(), 10.0 * angle.sin());
+        assert!(start.approx_eq_eps(&expected, 1e-2));
+    }
+    for polyline in dashed.flatten(0.001) {
+        for point in &polyline.points {
+            assert!((distance(point.x, point.y) - 10.0).abs() < 0.01);
+        }
+    }
+}
+
+#[test]
+fn path_measurement() {
+    use geometry::ApproxEq;
+
+    let mut path = CGMutablePath::new();
+    path.add_rect(CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(4.0, 3.0)));
+    path.move_to(CGPoint::new(10.0, 0.0));
+    path.add_arc(CGPoint::new(10.0, 10.0), 10.0, -PI * 0.5, 0.0, false);
+
+    let measure = path.measure(1e-9);
+    let quarter = 2.0 * PI * 10.0 / 4.0;
+    assert!((measure.length() - 14.0 - quarter).abs() < 0.01);
+
+    let position = measure.position_at(5.0).unwrap();
+    assert!(position.point.approx_eq(&CGPoint::new(4.0, 1.0)));
+    assert!(position.angle.approx_eq(&(PI * 0.5)));
+    // The closing line goes down the left edge.
+    let position = measure.position_at(13.0).unwrap();
+    assert!(position.point.approx_eq(&CGPoint::new(0.0, 1.0)));
+    assert!(position.angle.approx_eq(&(-PI * 0.5)));
+    assert_eq!(measure.position_at(-1.0).unwrap().point, CGPoint::new(0.0, 0.0));
+
+    // Half way along the arc, the tangent is at 45 degrees.
+    let middle = measure.position_at(14.0 + (measure.length() - 14.0) * 0.5).unwrap();
+    let expected = CGPoint::new(10.0 + 10.0 * (PI * 0.25).sin(), 10.0 - 10.0 * (PI * 0.25).cos());
+    assert!(middle.point.approx_eq_eps(&expected, 1e-3));
+    assert!(middle.angle.approx_eq_eps(&(PI * 0.25), 1e-3));
+    let end = measure.position_at(1000.0).unwrap();
+    assert!(end.point.approx_eq(&CGPoint::new(20.0, 10.0)));
+    assert_eq!(measure.position_at(CGFloat::INFINITY), Some(end));
+    assert_eq!(measure.position_at(CGFloat::NEG_INFINITY).unwrap().point, CGPoint::new(0.0, 0.0));
+    assert!(measure.position_at(CGFloat::NAN).is_none());
+
+    // A piece spanning both subpaths starts a new subpath at the gap.
+    let piece = measure.sub_path(12.0, measure.length() - 1.0).elements();
+    assert_eq!(piece[..3].to_vec(), vec![
+        PathElement::MoveTo(CGPoint::new(0.0, 2.0)),
+        PathElement::LineTo(CGPoint::new(0.0, 0.0)),
+        PathElement::MoveTo(CGPoint::new(10.0, 0.0)),
+    ]);
+    let piece_length = CGPath::from_elements(piece).measure(1e-9).length();
+    assert!((piece_length - (measure.length() - 13.0)).abs() < 1e-6);
+    assert!(measure.sub_path(5.0, 5.0).elements().is_empty());
+
+    assert!(CGMutablePath::new().measure(0.01).position_at(0.0).is_none());
+
+    // A coarser tolerance still gives a close length.
+    assert!((path.measure(0.1).length() - measure.length()).abs() < 0.1);
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn path_contains_point_matches_quartz() {
+    let mut path = CGMutablePath::new();
+    path.add_ellipse(CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(8.0, 6.0)));
+    path.move_to(CGPoint::new(2.0, 1.0));
+    path.curve_to(CGPoint::new(9.0, 2.0), CGPoint::new(-1.0, 4.0), CGPoint::new(6.0, 5.0));
+    path.quad_to(CGPoint::new(1.0, 7.0), CGPoint::new(2.0, 1.0));
+    let transform = CGAffineTransform::make_rotation(0.3).scale(1.5, 0.5);
+
+    for i in 0..40 {
+        for j in 0..40 {
+            let point = CGPoint::new(i as CGFloat * 0.25 - 0.3, j as CGFloat * 0.2 - 0.7);
+            for &(rule, eo_fill) in &[(CGPathFillRule::Winding, false),
+                                      (CGPathFillRule::EvenOdd, true)] {
+                for &m in &[None, Some(&transform)] {
+                    let expected = unsafe {
+                        let m = m.map_or(ptr::null(), |t| t as *const _);
+                        CGPathContainsPoint(path.as_ptr(), m, point, eo_fill)
+                    };
+                    assert_eq!(path.contains_point(point, m, rule), expected);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn path_bounds_match_quartz() {
+    use geometry::ApproxEq;
+
+    let mut path = CGMutablePath::new();
+    path.move_to(CGPoint::new(0.0, 0.0));
+    path.quad_to(CGPoint::new(1.0, 2.0), CGPoint::new(2.0, 0.0));
+    path.curve_to(CGPoint::new(2.0, -3.0), CGPoint::new(9.0, 4.0), CGPoint::new(6.0, 0.0));
+    path.add_arc(CGPoint::new(3.0, 3.0), 2.0, 0.3, 2.0, true);
+    path.add_rounded_rect(CGRect::new(&CGPoint::new(-5.0, 1.0), &CGSize::new(4.0, 3.0)),
+                          1.0,
+                          0.5);
+    let expected = unsafe { CGPathGetPathBoundingBox(path.as_ptr()) };
+    assert!(path.path_bounding_box().approx_eq(&expected));
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn path_stroke_matches_quartz() {
+    let mut path = CGMutablePath::new();
+    path.move_to(CGPoint::new(0.0, 0.0));
+    path.line_to(CGPoint::new(10.0, 0.0));
+    path.quad_to(CGPoint::new(12.0, 6.0), CGPoint::new(6.0, 8.0));
+    path.add_ellipse(CGRect::new(&CGPoint::new(1.0, 2.0), &CGSize::new(4.0, 3.0)));
+
+    let caps = [CGLineCap::Butt, CGLineCap::Round, CGLineCap::Square];
+    let joins = [CGLineJoin::Miter, CGLineJoin::Round, CGLineJoin::Bevel];
+    for (&cap, &join) in caps.iter().zip(&joins) {
+        let expected = path.stroke(1.5, cap, join, 4.0);
+        let approximation = path.approximate_stroke(1.5, cap, join, 4.0);
+        let inside = |path: &CGPathRef, x, y| {
+            path.contains_point(CGPoint::new(x, y), None, CGPathFillRule::Winding)
+        };
+        for i in 0..64 {
+            for j in 0..56 {
+                let (x, y) = (i as CGFloat * 0.23 - 1.5, j as CGFloat * 0.21 - 1.5);
+                // Skip points near the edges of the approximation, which the flattening moves.
+                let near = [(0.05, 0.0), (-0.05, 0.0), (0.0, 0.05), (0.0, -0.05)];
+                let inside_approximation = inside(&approximation, x, y);
+                if near.iter().any(|&(dx, dy)| inside(&approximation, x + dx, y + dy) !=
+                                                  inside_approximation) {
+                    continue;
+                }
+                assert_eq!(inside(&expected, x, y), inside_approximation, "({}, {})", x, y);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn path_shapes_match_quartz() {
+    use geometry::ApproxEq;
+
+    fn same_element(a: PathElement, b: PathElement) -> bool {
+        let same = |p: CGPoint, q: CGPoint| p.approx_eq_eps(&q, 1e-9);
+        match (a, b) {
+            (PathElement::MoveTo(p), PathElement::MoveTo(q)) |
+            (PathElement::LineTo(p), PathElement::LineTo(q)) => same(p, q),
+            (PathElement::QuadTo(c, p), PathElement::QuadTo(d, q)) => same(c, d) && same(p, q),
+            (PathElement::CubicTo(c1, c2, p), PathElement::CubicTo(d1, d2, q)) => {
+                same(c1, d1) && same(c2, d2) && same(p, q)
+            }
+            (PathElement::Close, PathElement::Close) => true,
+            _ => false,
+        }
+    }
+
+    fn assert_same(path: &CGPathRef, expected: Vec<PathElement>) {
+        let elements = path.elements();
+        assert!(elements.len() == expected.len() &&
+                    elements.iter().zip(&expected).all(|(&a, &b)| same_element(a, b)),
+                "{:?} != {:?}",
+                elements,
+                expected);
+    }
+
+    let rects = [
+        CGRect::new(&CGPoint::new(1.0, 2.0), &CGSize::new(8.0, 5.0)),
+        CGRect::new(&CGPoint::new(-4.0, -1.0), &CGSize::new(3.0, 6.0)),
+    ];
+    for rect in &rects {
+        let mut path = CGMutablePath::new();
+        path.add_rect(*rect);
+        assert_same(&path, rect_elements(*rect));
+
+        let mut path = CGMutablePath::new();
+        path.add_ellipse(*rect);
+        assert_same(&path, ellipse_elements(*rect));
+
+        for &(corner_width, corner_height) in &[(1.0, 0.5), (2.0, 2.0), (10.0, 10.0), (0.0, 1.0)] {
+            let mut path = CGMutablePath::new();
+            path.add_rounded_rect(*rect, corner_width, corner_height);
+            assert_same(&path, rounded_rect_elements(*rect, corner_width, corner_height));
+        }
+    }
+
+    let center = CGPoint::new(3.0, -2.0);
+    for &(start_angle, end_angle, clockwise) in &[(0.0, PI * 0.5, false),
+                                                   (0.3, 2.0, true),
+                                                   (-1.0, 4.0, false),
+                                                   (2.5, -2.5, false)] {
+        let mut path = CGMutablePath::new();
+        path.add_arc(center, 4.0, start_angle, end_angle, clockwise);
+        let (start, curves) = arc_elements(center, 4.0, start_angle, end_angle, clockwise);
+        let mut expected = vec![PathElement::MoveTo(start)];
+        expected.extend(curves);
+        assert_same(&path, expected);
+    }
+}
+
+#[cfg(target_os = "macos")]
 type CGPathApplierFunction = unsafe extern "C" fn(info: *mut c_void,
                                                   element: *const CGPathElement);
 
+#[cfg(target_os = "macos")]
 #[link(name = "CoreGraphics", kind = "framework")]
 extern {
     fn CGPathApply(path: ::sys::CGPathRef, info: *mut c_void, function: CGPathApplierFunction);
     fn CGPathGetTypeID() -> CFTypeID;
+    fn CGPathGetBoundingBox(path: ::sys::CGPathRef) -> CGRect;
+    #[cfg(test)]
+    fn CGPathGetPathBoundingBox(path: ::sys::CGPathRef) -> CGRect;
+    #[cfg(test)]
+    fn CGPathContainsPoint(path: ::sys::CGPathRef,
+                           m: *const CGAffineTransform,
+                           point: CGPoint,
+                           eoFill: bool)
+                           -> bool;
+
+    fn CGPathCreateMutable() -> ::sys::CGPathRef;
+    fn CGPathIsEmpty(path: ::sys::CGPathRef) -> bool;
+    fn CGPathGetCurrentPoint(path: ::sys::CGPathRef) -> CGPoint;
+    fn CGPathMoveToPoint(path: ::sys::CGPathRef,
+                         m: *const CGAffineTransform,
+                         x: CGFloat,
+                         y: CGFloat);
+    fn CGPathAddLineToPoint(path: ::sys::CGPathRef,
+                            m: *const CGAffineTransform,
+                            x: CGFloat,
+                            y: CGFloat);
+    fn CGPathAddQuadCurveToPoint(path: ::sys::CGPathRef,
+                                 m: *const CGAffineTransform,
+                                 cpx: CGFloat,
+                                 cpy: CGFloat,
+                                 x: CGFloat,
+                                 y: CGFloat);
+    fn CGPathAddCurveToPoint(path: ::sys::CGPathRef,
+                             m: *const CGAffineTransform,
+                             cp1x: CGFloat,
+                             cp1y: CGFloat,
+                             cp2x: CGFloat,
+                             cp2y: CGFloat,
+                             x: CGFloat,
+                             y: CGFloat);
+    fn CGPathCloseSubpath(path: ::sys::CGPathRef);
+    fn CGPathAddRect(path: ::sys::CGPathRef, m: *const CGAffineTransform, rect: CGRect);
+    fn CGPathAddEllipseInRect(path: ::sys::CGPathRef, m: *const CGAffineTransform, rect: CGRect);
+    fn CGPathAddArc(path: ::sys::CGPathRef,
+                    m: *const CGAffineTransform,
+                    x: CGFloat,
+                    y: CGFloat,
+                    radius: CGFloat,
+                    startAngle: CGFloat,
+                    endAngle: CGFloat,
+                    clockwise: bool);
+    fn CGPathAddRoundedRect(path: ::sys::CGPathRef,
+                            m: *const CGAffineTransform,
+                            rect: CGRect,
+                            cornerWidth: CGFloat,
+                            cornerHeight: CGFloat);
+    fn CGPathAddPath(path1: ::sys::CGPathRef,
+                     m: *const CGAffineTransform,
+                     path2: ::sys::CGPathRef);
+    fn CGPathCreateCopyByStrokingPath(path: ::sys::CGPathRef,
+                                      transform: *const CGAffineTransform,
+                                      lineWidth: CGFloat,
+                                      lineCap: CGLineCap,
+                                      lineJoin: CGLineJoin,
+                                      miterLimit: CGFloat)
+                                      -> ::sys::CGPathRef;
 }
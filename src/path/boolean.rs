@@ -0,0 +1,347 @@
+// Copyright 2018 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Boolean operations on the filled areas of paths.
+//!
+//! Both paths are flattened, every edge is split where it meets another one, and the pieces
+//! that separate the inside of the result from its outside are kept and linked up into closed
+//! polygons.
+
+use base::CGFloat;
+use geometry::{BitKey, CGPoint};
+use std::collections::{HashMap, HashSet};
+use super::{CGMutablePath, CGPath, CGPathFillRule, CGPathRef};
+use super::{add, direction, distance, left_normal, line_crossings, scale, sub};
+
+type Edge = (CGPoint, CGPoint);
+
+impl CGPathRef {
+    /// Returns a path filling the area filled by either path, like
+    /// `CGPathCreateCopyByUnioningPath`.
+    ///
+    /// Both paths are filled with `rule`. Curves are flattened with a tolerance of 1/10000 of the
+    /// size of the two paths' combined bounds, so the result only has lines. Its subpaths are
+    /// closed polygons going counterclockwise (in a y-up coordinate system) around filled areas
+    /// and clockwise around holes, so it can be filled with either rule.
+    #[inline]
+    pub fn union(&self, other: &CGPathRef, rule: CGPathFillRule) -> CGPath {
+        self.combine(other, rule, |a, b| a || b)
+    }
+
+    /// Returns a path filling the area filled by both paths. See `union` for the details.
+    #[inline]
+    pub fn intersection(&self, other: &CGPathRef, rule: CGPathFillRule) -> CGPath {
+        self.combine(other, rule, |a, b| a && b)
+    }
+
+    /// Returns a path filling the area filled by this path but not by `other`. See `union` for
+    /// the details.
+    #[inline]
+    pub fn subtract(&self, other: &CGPathRef, rule: CGPathFillRule) -> CGPath {
+        self.combine(other, rule, |a, b| a && !b)
+    }
+
+    /// Returns a path filling the area filled by exactly one of the two paths. See `union` for
+    /// the details.
+    #[inline]
+    pub fn xor(&self, other: &CGPathRef, rule: CGPathFillRule) -> CGPath {
+        self.combine(other, rule, |a, b| a != b)
+    }
+
+    /// Computes the outline of the points for which `op` returns true, given whether the point
+    /// is in `self` and in `other`.
+    fn combine<F>(&self, other: &CGPathRef, rule: CGPathFillRule, op: F) -> CGPath
+                  where F: Fn(bool, bool) -> bool {
+        let bounds = self.path_bounding_box().union(&other.path_bounding_box());
+        if bounds.is_null() {
+            return CGMutablePath::new().into_path();
+        }
+        let size = bounds.size.width.max(bounds.size.height);
+        if size <= 0.0 || !size.is_finite() {
+            return CGMutablePath::new().into_path();
+        }
+
+        let a = polygon_edges(self, size * 1e-4);
+        let b = polygon_edges(other, size * 1e-4);
+        let mut all = a.clone();
+        all.extend_from_slice(&b);
+
+        // Each piece of an edge is kept if the result changes from inside to outside across it,
+        // oriented so that the inside is on its left.
+        let offset = size * 1e-7;
+        let inside = |point: CGPoint| {
+            op(is_inside(&a, point, rule), is_inside(&b, point, rule))
+        };
+        let mut kept = vec![];
+        let mut seen = HashSet::new();
+        for (from, to) in split_edges(&all, size * 1e-9) {
+            let middle = scale(add(from, to), 0.5);
+            let normal = scale(left_normal(direction(from, to)), offset);
+            let edge = match (inside(add(middle, normal)), inside(sub(middle, normal))) {
+                (true, false) => (from, to),
+                (false, true) => (to, from),
+                _ => continue,
+            };
+            // Edges shared by both paths would otherwise show up twice.
+            if seen.insert((BitKey(edge.0), BitKey(edge.1))) {
+                kept.push(edge);
+            }
+        }
+
+        let mut path = CGMutablePath::new();
+        for polygon in link_edges(&kept) {
+            let polygon = remove_collinear_points(polygon);
+            if polygon.len() < 3 {
+                continue;
+            }
+            path.move_to(polygon[0]);
+            for &point in &polygon[1..] {
+                path.line_to(point);
+            }
+            path.close();
+        }
+        path.into_path()
+    }
+}
+
+/// Returns the edges of the flattened path, with every subpath closed as when filling.
+fn polygon_edges(path: &CGPathRef, tolerance: CGFloat) -> Vec<Edge> {
+    let mut edges = vec![];
+    for polyline in path.flatten(tolerance) {
+        let points = &polyline.points;
+        for i in 0..points.len() {
+            let (from, to) = (points[i], points[(i + 1) % points.len()]);
+            if from != to {
+                edges.push((from, to));
+            }
+        }
+    }
+    edges
+}
+
+fn is_inside(edges: &[Edge], point: CGPoint, rule: CGPathFillRule) -> bool {
+    let winding: i32 = edges.iter().map(|&(from, to)| line_crossings(from, to, point)).sum();
+    match rule {
+        CGPathFillRule::Winding => winding != 0,
+        CGPathFillRule::EvenOdd => winding % 2 != 0,
+    }
+}
+
+#[inline]
+fn cross(a: CGPoint, b: CGPoint) -> CGFloat {
+    a.x * b.y - a.y * b.x
+}
+
+/// Splits the edges wherever they cross or touch another edge, so that edges only meet at their
+/// end points. Split points are shared exactly by all the pieces ending at them.
+fn split_edges(edges: &[Edge], snap: CGFloat) -> Vec<Edge> {
+    let mut splits: Vec<Vec<(CGFloat, CGPoint)>> = vec![vec![]; edges.len()];
+    for i in 0..edges.len() {
+        let (a0, a1) = edges[i];
+        for j in i + 1..edges.len() {
+            let (b0, b1) = edges[j];
+            if a0.x.min(a1.x) > b0.x.max(b1.x) + snap || b0.x.min(b1.x) > a0.x.max(a1.x) + snap ||
+               a0.y.min(a1.y) > b0.y.max(b1.y) + snap || b0.y.min(b1.y) > a0.y.max(a1.y) + snap {
+                continue;
+            }
+
+            // End points lying on the other edge, which covers touching and overlapping edges.
+            for &(point, edge) in &[(b0, i), (b1, i), (a0, j), (a1, j)] {
+                if let Some(t) = parameter_on_edge(edges[edge], point, snap) {
+                    splits[edge].push((t, point));
+                }
+            }
+
+            // Proper crossings in the interior of both edges.
+            let (da, db) = (sub(a1, a0), sub(b1, b0));
+            let denominator = cross(da, db);
+            if denominator == 0.0 {
+                continue;
+            }
+            let t = cross(sub(b0, a0), db) / denominator;
+            let u = cross(sub(b0, a0), da) / denominator;
+            let point = add(a0, scale(da, t));
+            let interior = |t: CGFloat, (from, to): Edge| {
+                t > 0.0 && t < 1.0 && distance(point.x - from.x, point.y - from.y) > snap &&
+                    distance(point.x - to.x, point.y - to.y) > snap
+            };
+            if interior(t, edges[i]) && interior(u, edges[j]) {
+                splits[i].push((t, point));
+                splits[j].push((u, point));
+            }
+        }
+    }
+
+    let mut pieces = vec![];
+    for (&(from, to), edge_splits) in edges.iter().zip(splits.iter_mut()) {
+        edge_splits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut start = from;
+        for &(_, point) in edge_splits.iter().chain(Some(&(1.0, to))) {
+            if point != start {
+                pieces.push((start, point));
+                start = point;
+            }
+        }
+    }
+    pieces
+}
+
+/// Returns the parameter along `edge` of `point` if it lies on the edge, within `snap`, and
+/// strictly between its end points.
+fn parameter_on_edge((from, to): Edge, point: CGPoint, snap: CGFloat) -> Option<CGFloat> {
+    let d = sub(to, from);
+    let length_squared = d.x * d.x + d.y * d.y;
+    let t = ((point.x - from.x) * d.x + (point.y - from.y) * d.y) / length_squared;
+    if !(t > 0.0 && t < 1.0) || point == from || point == to {
+        return None;
+    }
+    let closest = add(from, scale(d, t));
+    if distance(point.x - closest.x, point.y - closest.y) <= snap {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Links directed edges into closed polygons by following them from end point to start point.
+fn link_edges(edges: &[Edge]) -> Vec<Vec<CGPoint>> {
+    let mut starting_at: HashMap<BitKey<CGPoint>, Vec<usize>> = HashMap::new();
+    for (index, edge) in edges.iter().enumerate() {
+        starting_at.entry(BitKey(edge.0)).or_default().push(index);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut polygons = vec![];
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        let start = edges[first].0;
+        let mut polygon = vec![start];
+        let mut current = first;
+        loop {
+            used[current] = true;
+            let end = edges[current].1;
+            if end == start {
+                break;
+            }
+            polygon.push(end);
+            let next = starting_at.get(&BitKey(end)).and_then(|candidates| {
+                candidates.iter().cloned().find(|&candidate| !used[candidate])
+            });
+            match next {
+                Some(next) => current = next,
+                // Only reachable through rounding errors; the polygon is closed as it is.
+                None => break,
+            }
+        }
+        polygons.push(polygon);
+    }
+    polygons
+}
+
+/// Removes the vertices of a closed polygon at which it goes straight on.
+fn remove_collinear_points(polygon: Vec<CGPoint>) -> Vec<CGPoint> {
+    let count = polygon.len();
+    if count < 3 {
+        return polygon;
+    }
+    (0..count).filter(|&i| {
+        let (previous, point, next) = (polygon[(i + count - 1) % count],
+                                       polygon[i],
+                                       polygon[(i + 1) % count]);
+        let (incoming, outgoing) = (direction(previous, point), direction(point, next));
+        cross(incoming, outgoing).abs() > 1e-9 ||
+            incoming.x * outgoing.x + incoming.y * outgoing.y < 0.0
+    }).map(|i| polygon[i]).collect()
+}
+
+#[cfg(test)]
+fn square(x: CGFloat, y: CGFloat, size: CGFloat) -> CGPath {
+    use geometry::{CGRect, CGSize};
+
+    let mut path = CGMutablePath::new();
+    path.add_rect(CGRect::new(&CGPoint::new(x, y), &CGSize::new(size, size)));
+    path.into_path()
+}
+
+#[test]
+fn path_boolean_operations() {
+    let a = square(0.0, 0.0, 10.0);
+    let b = square(5.0, 5.0, 10.0);
+    let rule = CGPathFillRule::Winding;
+    let inside = |path: &CGPath, x, y| {
+        let winding = path.contains_point(CGPoint::new(x, y), None, CGPathFillRule::Winding);
+        assert_eq!(winding, path.contains_point(CGPoint::new(x, y), None, CGPathFillRule::EvenOdd));
+        winding
+    };
+    let samples = [(2.0, 2.0), (7.0, 7.0), (12.0, 12.0), (2.0, 12.0), (12.0, 2.0), (-1.0, 5.0)];
+    let check = |path: &CGPath, op: fn(bool, bool) -> bool| {
+        for &(x, y) in &samples {
+            let expected = op(inside(&a, x, y), inside(&b, x, y));
+            assert_eq!(inside(path, x, y), expected, "at ({}, {})", x, y);
+        }
+    };
+
+    let union = a.union(&b, rule);
+    check(&union, |a, b| a || b);
+    // One eight-sided outline, without the vertices where the edges were split.
+    assert_eq!(union.elements().len(), 9);
+    check(&a.intersection(&b, rule), |a, b| a && b);
+    assert_eq!(a.intersection(&b, rule).elements().len(), 5);
+    check(&a.subtract(&b, rule), |a, b| a && !b);
+    check(&a.xor(&b, rule), |a, b| a != b);
+    assert_eq!(b.subtract(&a, rule).path_bounding_box(), b.path_bounding_box());
+
+    // Shapes sharing edges.
+    let same = a.union(&a, rule);
+    assert_eq!(same.path_bounding_box(), a.path_bounding_box());
+    assert_eq!(same.elements().len(), 5);
+    assert!(a.subtract(&a, rule).elements().is_empty());
+    let neighbor = square(10.0, 0.0, 10.0);
+    let wide = a.union(&neighbor, rule);
+    assert_eq!(wide.elements().len(), 5);
+    assert!(inside(&wide, 10.0, 5.0));
+
+    // A hole, from a square inside another one.
+    let ring = a.subtract(&square(3.0, 3.0, 4.0), rule);
+    assert!(inside(&ring, 1.0, 5.0));
+    assert!(!inside(&ring, 5.0, 5.0));
+    assert_eq!(ring.flatten(0.1).len(), 2);
+    assert!(a.intersection(&square(20.0, 20.0, 1.0), rule).elements().is_empty());
+    assert!(CGMutablePath::new().union(&CGMutablePath::new(), rule).elements().is_empty());
+}
+
+#[test]
+fn path_boolean_curves_and_fill_rules() {
+    use geometry::{CGRect, CGSize};
+
+    let mut circle = CGMutablePath::new();
+    circle.add_ellipse(CGRect::new(&CGPoint::new(-10.0, -10.0), &CGSize::new(20.0, 20.0)));
+    let half_plane = square(0.0, -20.0, 40.0);
+    let half = circle.intersection(&half_plane, CGPathFillRule::Winding);
+    for &(x, y, expected) in &[(5.0, 0.0, true), (-5.0, 0.0, false), (9.9, 0.0, true),
+                               (0.5, 9.9, true), (0.5, 10.1, false), (7.0, 7.0, true),
+                               (7.2, 7.2, false)] {
+        let actual = half.contains_point(CGPoint::new(x, y), None, CGPathFillRule::Winding);
+        assert_eq!(actual, expected, "at ({}, {})", x, y);
+    }
+
+    // Two nested squares going the same way, which only the even-odd rule sees as a ring.
+    let mut nested = CGMutablePath::new();
+    nested.add_path(&square(0.0, 0.0, 10.0), None);
+    nested.add_path(&square(3.0, 3.0, 4.0), None);
+    let small = square(4.0, 4.0, 2.0);
+    let winding = nested.union(&small, CGPathFillRule::Winding);
+    let even_odd = nested.union(&small, CGPathFillRule::EvenOdd);
+    let center = CGPoint::new(3.5, 5.0);
+    assert!(winding.contains_point(center, None, CGPathFillRule::Winding));
+    assert!(!even_odd.contains_point(center, None, CGPathFillRule::Winding));
+    assert!(even_odd.contains_point(CGPoint::new(5.0, 5.0), None, CGPathFillRule::Winding));
+}
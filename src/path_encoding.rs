@@ -0,0 +1,462 @@
+// Copyright 2018 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A compact binary encoding of paths, e.g. to cache outlines on disk.
+//!
+//! An encoded path is a frame made of a 20-byte header followed by a payload. All values are
+//! little-endian:
+//!
+//! | Offset | Size | Contents                                                          |
+//! |--------|------|-------------------------------------------------------------------|
+//! | 0      | 4    | The magic bytes `CGPB`                                            |
+//! | 4      | 1    | The format version, currently 1                                   |
+//! | 5      | 1    | The size of coordinates, 4 for `f32` or 8 for `f64`               |
+//! | 6      | 2    | Zero                                                              |
+//! | 8      | 4    | The number of elements                                            |
+//! | 12     | 4    | The length of the payload in bytes                                |
+//! | 16     | 4    | The 32-bit FNV-1a hash of the payload                             |
+//!
+//! The payload has one byte per element, holding its `CGPathElementType`, followed by the
+//! coordinates of the points of all elements in order, `x` before `y`.
+//!
+//! Frames can be concatenated, and `CGPath::decode_prefix` reads them one at a time.
+
+use base::CGFloat;
+use geometry::CGPoint;
+use path::{CGPath, CGPathElementType, CGPathRef, PathElement};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+const MAGIC: &[u8; 4] = b"CGPB";
+const VERSION: u8 = 1;
+const HEADER_LENGTH: usize = 20;
+
+/// The type coordinates are stored as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathPrecision {
+    /// 32-bit floats, which halves the size of the coordinates but rounds them.
+    Single,
+    /// 64-bit floats, which keep coordinates exactly.
+    Double,
+}
+
+impl PathPrecision {
+    #[inline]
+    fn coordinate_size(self) -> usize {
+        match self {
+            PathPrecision::Single => 4,
+            PathPrecision::Double => 8,
+        }
+    }
+}
+
+/// The reason an encoded path could not be decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathDecodeError {
+    /// The data ends before the end of the frame.
+    Truncated { needed: usize, available: usize },
+    /// The data doesn't start with the magic bytes.
+    InvalidMagic,
+    /// The frame was written by a newer version of the format.
+    UnsupportedVersion(u8),
+    /// The coordinate size is neither 4 nor 8.
+    InvalidPrecision(u8),
+    /// The reserved header bytes are not zero.
+    InvalidHeader,
+    /// The payload length doesn't match the number and types of the elements. `expected` is
+    /// `usize::MAX` if the length they need doesn't fit in a `usize`.
+    LengthMismatch { expected: usize, actual: usize },
+    /// The payload doesn't match its hash.
+    ChecksumMismatch,
+    /// An element has an unknown type.
+    InvalidElementType { index: usize, element_type: u8 },
+    /// The first element is not a move.
+    MissingMoveTo,
+    /// A coordinate is infinite or NaN.
+    NonFiniteCoordinate { index: usize },
+    /// `CGPath::decode` was given data continuing after the frame.
+    TrailingData { length: usize },
+}
+
+impl Display for PathDecodeError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            PathDecodeError::Truncated { needed, available } => {
+                write!(formatter, "expected {} bytes but only {} are available", needed, available)
+            }
+            PathDecodeError::InvalidMagic => write!(formatter, "data is not an encoded path"),
+            PathDecodeError::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported encoded path version {}", version)
+            }
+            PathDecodeError::InvalidPrecision(size) => {
+                write!(formatter, "invalid coordinate size {}", size)
+            }
+            PathDecodeError::InvalidHeader => write!(formatter, "reserved header bytes are set"),
+            PathDecodeError::LengthMismatch { expected, actual } => {
+                write!(formatter, "payload is {} bytes long instead of {}", actual, expected)
+            }
+            PathDecodeError::ChecksumMismatch => write!(formatter, "payload checksum mismatch"),
+            PathDecodeError::InvalidElementType { index, element_type } => {
+                write!(formatter, "element {} has invalid type {}", index, element_type)
+            }
+            PathDecodeError::MissingMoveTo => write!(formatter, "path must start with a move"),
+            PathDecodeError::NonFiniteCoordinate { index } => {
+                write!(formatter, "element {} has a non-finite coordinate", index)
+            }
+            PathDecodeError::TrailingData { length } => {
+                write!(formatter, "{} bytes follow the encoded path", length)
+            }
+        }
+    }
+}
+
+impl Error for PathDecodeError {}
+
+/// The reason a path could not be encoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathEncodeError {
+    /// The number of elements or the length of the payload doesn't fit in the 32-bit fields of
+    /// the header.
+    TooLarge { elements: usize, payload_length: usize },
+}
+
+impl Display for PathEncodeError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            PathEncodeError::TooLarge { elements, payload_length } => {
+                write!(formatter,
+                       "path of {} elements with a {} byte payload is too large to encode",
+                       elements,
+                       payload_length)
+            }
+        }
+    }
+}
+
+impl Error for PathEncodeError {}
+
+impl CGPathRef {
+    /// Returns the path encoded as a single frame, or an error if it is too large for the
+    /// header to describe.
+    ///
+    /// Coordinates are not checked: infinite and NaN values, including `f64` values too large
+    /// for `PathPrecision::Single`, are written as they are and make decoding fail.
+    pub fn encode(&self, precision: PathPrecision) -> Result<Vec<u8>, PathEncodeError> {
+        let mut data = vec![];
+        self.encode_into(precision, &mut data)?;
+        Ok(data)
+    }
+
+    /// Appends the path encoded as a single frame to `data`. If the path is too large to
+    /// encode, `data` is left unchanged.
+    pub fn encode_into(&self, precision: PathPrecision, data: &mut Vec<u8>)
+                       -> Result<(), PathEncodeError> {
+        let elements = self.elements();
+        let mut types = Vec::with_capacity(elements.len());
+        let mut coordinates = vec![];
+        for element in &elements {
+            use path::CGPathElementType::*;
+            let (element_type, points) = match *element {
+                PathElement::MoveTo(p) => (MoveToPoint, vec![p]),
+                PathElement::LineTo(p) => (AddLineToPoint, vec![p]),
+                PathElement::QuadTo(c, p) => (AddQuadCurveToPoint, vec![c, p]),
+                PathElement::CubicTo(c1, c2, p) => (AddCurveToPoint, vec![c1, c2, p]),
+                PathElement::Close => (CloseSubpath, vec![]),
+            };
+            types.push(element_type as u8);
+            for point in points {
+                for &value in &[point.x, point.y] {
+                    match precision {
+                        PathPrecision::Single => {
+                            let bits = (value as f32).to_bits();
+                            coordinates.extend_from_slice(&bits.to_le_bytes())
+                        }
+                        PathPrecision::Double => {
+                            #[allow(clippy::unnecessary_cast)]
+                            let bits = (value as f64).to_bits();
+                            coordinates.extend_from_slice(&bits.to_le_bytes())
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut payload = types;
+        payload.extend_from_slice(&coordinates);
+        let (count, payload_length) = header_lengths(elements.len(), payload.len())?;
+        data.reserve(HEADER_LENGTH + payload.len());
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.push(precision.coordinate_size() as u8);
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(&payload_length.to_le_bytes());
+        data.extend_from_slice(&fnv1a(&payload).to_le_bytes());
+        data.extend_from_slice(&payload);
+        Ok(())
+    }
+}
+
+impl CGPath {
+    /// Decodes a path encoded with `CGPathRef::encode`. `data` must hold exactly one frame.
+    pub fn decode(data: &[u8]) -> Result<CGPath, PathDecodeError> {
+        let (path, length) = CGPath::decode_prefix(data)?;
+        if length != data.len() {
+            return Err(PathDecodeError::TrailingData { length: data.len() - length });
+        }
+        Ok(path)
+    }
+
+    /// Decodes the frame at the start of `data`, returning the path and the length of the
+    /// frame, so that further frames can be decoded from the rest of the data.
+    ///
+    /// The whole frame is validated before the path is built.
+    pub fn decode_prefix(data: &[u8]) -> Result<(CGPath, usize), PathDecodeError> {
+        let header = take(data, 0, HEADER_LENGTH)?;
+        if &header[0..4] != MAGIC {
+            return Err(PathDecodeError::InvalidMagic);
+        }
+        if header[4] != VERSION {
+            return Err(PathDecodeError::UnsupportedVersion(header[4]));
+        }
+        let precision = match header[5] {
+            4 => PathPrecision::Single,
+            8 => PathPrecision::Double,
+            size => return Err(PathDecodeError::InvalidPrecision(size)),
+        };
+        if header[6] != 0 || header[7] != 0 {
+            return Err(PathDecodeError::InvalidHeader);
+        }
+        let count = read_u32(&header[8..12]) as usize;
+        let payload_length = read_u32(&header[12..16]) as usize;
+        let checksum = read_u32(&header[16..20]);
+
+        // The payload length is checked against the data before anything is allocated, so that
+        // a corrupted count can't cause a huge allocation.
+        let payload = take(data, HEADER_LENGTH, payload_length)?;
+        if fnv1a(payload) != checksum {
+            return Err(PathDecodeError::ChecksumMismatch);
+        }
+        if count > payload_length {
+            return Err(PathDecodeError::LengthMismatch {
+                expected: count,
+                actual: payload_length,
+            });
+        }
+        let (types, coordinates) = payload.split_at(count);
+
+        let mut element_types = Vec::with_capacity(count);
+        let mut point_count = 0usize;
+        for (index, &value) in types.iter().enumerate() {
+            let (element_type, points) = match value {
+                0 => (CGPathElementType::MoveToPoint, 1),
+                1 => (CGPathElementType::AddLineToPoint, 1),
+                2 => (CGPathElementType::AddQuadCurveToPoint, 2),
+                3 => (CGPathElementType::AddCurveToPoint, 3),
+                4 => (CGPathElementType::CloseSubpath, 0),
+                _ => {
+                    return Err(PathDecodeError::InvalidElementType {
+                        index: index,
+                        element_type: value,
+                    })
+                }
+            };
+            if index == 0 && element_type != CGPathElementType::MoveToPoint {
+                return Err(PathDecodeError::MissingMoveTo);
+            }
+            element_types.push(element_type);
+            point_count = point_count.saturating_add(points);
+        }
+        let expected = point_count.checked_mul(2 * precision.coordinate_size())
+                                  .and_then(|length| length.checked_add(count))
+                                  .unwrap_or(usize::MAX);
+        if payload_length != expected {
+            return Err(PathDecodeError::LengthMismatch {
+                expected: expected,
+                actual: payload_length,
+            });
+        }
+
+        let mut values = coordinates.chunks(precision.coordinate_size()).map(|bytes| {
+            match precision {
+                PathPrecision::Single => f32::from_bits(read_u32(bytes)) as CGFloat,
+                PathPrecision::Double => f64::from_bits(read_u64(bytes)) as CGFloat,
+            }
+        });
+        let mut elements = Vec::with_capacity(count);
+        for (index, element_type) in element_types.into_iter().enumerate() {
+            let mut point = || {
+                let (x, y) = (values.next().unwrap(), values.next().unwrap());
+                if x.is_finite() && y.is_finite() {
+                    Ok(CGPoint::new(x, y))
+                } else {
+                    Err(PathDecodeError::NonFiniteCoordinate { index: index })
+                }
+            };
+            elements.push(match element_type {
+                CGPathElementType::MoveToPoint => PathElement::MoveTo(point()?),
+                CGPathElementType::AddLineToPoint => PathElement::LineTo(point()?),
+                CGPathElementType::AddQuadCurveToPoint => {
+                    let control = point()?;
+                    PathElement::QuadTo(control, point()?)
+                }
+                CGPathElementType::AddCurveToPoint => {
+                    let (control_1, control_2) = (point()?, point()?);
+                    PathElement::CubicTo(control_1, control_2, point()?)
+                }
+                CGPathElementType::CloseSubpath => PathElement::Close,
+            });
+        }
+        Ok((CGPath::from_elements(elements), HEADER_LENGTH + payload_length))
+    }
+}
+
+/// Returns the number of elements and the payload length as stored in the header.
+fn header_lengths(elements: usize, payload_length: usize)
+                  -> Result<(u32, u32), PathEncodeError> {
+    match (u32::try_from(elements), u32::try_from(payload_length)) {
+        (Ok(elements), Ok(payload_length)) => Ok((elements, payload_length)),
+        _ => {
+            Err(PathEncodeError::TooLarge {
+                elements: elements,
+                payload_length: payload_length,
+            })
+        }
+    }
+}
+
+/// Returns `length` bytes of `data` from `offset`.
+fn take(data: &[u8], offset: usize, length: usize) -> Result<&[u8], PathDecodeError> {
+    match offset.checked_add(length) {
+        Some(end) if end <= data.len() => Ok(&data[offset..end]),
+        _ => {
+            Err(PathDecodeError::Truncated {
+                needed: offset.saturating_add(length),
+                available: data.len(),
+            })
+        }
+    }
+}
+
+#[inline]
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[inline]
+fn read_u64(bytes: &[u8]) -> u64 {
+    (read_u32(&bytes[0..4]) as u64) | ((read_u32(&bytes[4..8]) as u64) << 32)
+}
+
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193))
+}
+
+#[test]
+fn path_encoding_round_trip() {
+    use path::CGMutablePath;
+
+    let mut path = CGMutablePath::new();
+    path.move_to(CGPoint::new(0.1, -2.0));
+    path.line_to(CGPoint::new(10.0, 0.0));
+    path.quad_to(CGPoint::new(15.0, 5.0), CGPoint::new(10.0, 10.0));
+    path.curve_to(CGPoint::new(5.0, 15.0), CGPoint::new(1.0, 12.5), CGPoint::new(0.0, 10.0));
+    path.close();
+    path.move_to(CGPoint::new(20.0, 20.0));
+    path.line_to(CGPoint::new(30.0, 20.0));
+    let path = path.into_path();
+
+    let double = path.encode(PathPrecision::Double).unwrap();
+    assert_eq!(double.len(), HEADER_LENGTH + 7 + 2 * 9 * 8);
+    assert_eq!(CGPath::decode(&double).unwrap().elements(), path.elements());
+
+    let single = path.encode(PathPrecision::Single).unwrap();
+    assert_eq!(single.len(), HEADER_LENGTH + 7 + 2 * 9 * 4);
+    let decoded = CGPath::decode(&single).unwrap().elements();
+    assert_eq!(decoded.len(), 7);
+    assert_eq!(decoded[0], PathElement::MoveTo(CGPoint::new(0.1f32 as CGFloat, -2.0)));
+    assert_eq!(decoded[1..], path.elements()[1..]);
+
+    let empty = CGMutablePath::new().encode(PathPrecision::Single).unwrap();
+    assert_eq!(empty.len(), HEADER_LENGTH);
+    assert!(CGPath::decode(&empty).unwrap().elements().is_empty());
+
+    // Concatenated frames.
+    let mut data = vec![];
+    path.encode_into(PathPrecision::Single, &mut data).unwrap();
+    path.encode_into(PathPrecision::Double, &mut data).unwrap();
+    let (first, length) = CGPath::decode_prefix(&data).unwrap();
+    assert_eq!(length, single.len());
+    assert_eq!(first.elements(), decoded);
+    let (second, rest) = CGPath::decode_prefix(&data[length..]).unwrap();
+    assert_eq!(length + rest, data.len());
+    assert_eq!(second.elements(), path.elements());
+    assert_eq!(CGPath::decode(&data).err(),
+               Some(PathDecodeError::TrailingData { length: double.len() }));
+
+    // Lengths that don't fit in the header are rejected rather than truncated.
+    assert_eq!(header_lengths(7, 79), Ok((7, 79)));
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(header_lengths(1 << 32, 79),
+               Err(PathEncodeError::TooLarge { elements: 1 << 32, payload_length: 79 }));
+}
+
+#[test]
+fn path_encoding_validation() {
+    use path::CGMutablePath;
+
+    let mut path = CGMutablePath::new();
+    path.move_to(CGPoint::new(1.0, 2.0));
+    path.line_to(CGPoint::new(3.0, 4.0));
+    path.close();
+    let data = path.encode(PathPrecision::Single).unwrap();
+
+    // Rewrites the payload and its hash, which only leaves the structural checks.
+    let with_payload = |change: &dyn Fn(&mut Vec<u8>)| {
+        let mut payload = data[HEADER_LENGTH..].to_vec();
+        change(&mut payload);
+        let mut data = data[..HEADER_LENGTH].to_vec();
+        data[12..16].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        data[16..20].copy_from_slice(&fnv1a(&payload).to_le_bytes());
+        data.extend_from_slice(&payload);
+        CGPath::decode(&data).map(|path| path.elements())
+    };
+    let with_header = |offset: usize, value: u8| {
+        let mut data = data.clone();
+        data[offset] = value;
+        CGPath::decode(&data).map(|path| path.elements())
+    };
+
+    assert_eq!(CGPath::decode(&data[..10]).err(),
+               Some(PathDecodeError::Truncated { needed: HEADER_LENGTH, available: 10 }));
+    assert_eq!(CGPath::decode(&data[..data.len() - 1]).err(),
+               Some(PathDecodeError::Truncated { needed: data.len(), available: data.len() - 1 }));
+    assert_eq!(with_header(0, b'X').unwrap_err(), PathDecodeError::InvalidMagic);
+    assert_eq!(with_header(4, 2).unwrap_err(), PathDecodeError::UnsupportedVersion(2));
+    assert_eq!(with_header(5, 2).unwrap_err(), PathDecodeError::InvalidPrecision(2));
+    assert_eq!(with_header(7, 1).unwrap_err(), PathDecodeError::InvalidHeader);
+    assert_eq!(with_header(14, 1).unwrap_err(),
+               PathDecodeError::Truncated { needed: HEADER_LENGTH + 0x10013, available: 39 });
+    assert_eq!(with_header(HEADER_LENGTH + 5, 0x7f).unwrap_err(),
+               PathDecodeError::ChecksumMismatch);
+    assert_eq!(with_header(8, 4).unwrap_err(),
+               PathDecodeError::LengthMismatch { expected: 4 + 3 * 8, actual: 19 });
+
+    assert_eq!(with_payload(&|payload| payload[1] = 9).unwrap_err(),
+               PathDecodeError::InvalidElementType { index: 1, element_type: 9 });
+    assert_eq!(with_payload(&|payload| payload[0] = 1).unwrap_err(),
+               PathDecodeError::MissingMoveTo);
+    assert_eq!(with_payload(&|payload| payload.truncate(18)).unwrap_err(),
+               PathDecodeError::LengthMismatch { expected: 19, actual: 18 });
+    assert_eq!(with_payload(&|payload| {
+        payload[11..15].copy_from_slice(&f32::NAN.to_bits().to_le_bytes())
+    }).unwrap_err(), PathDecodeError::NonFiniteCoordinate { index: 1 });
+    assert_eq!(with_payload(&|_| {}).unwrap(), path.elements());
+}
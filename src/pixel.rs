@@ -0,0 +1,866 @@
+// Copyright 2018 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversion of RGB pixel data between the layouts described by `CGBitmapInfo`, e.g. from
+//! the BGRA data of a screenshot to straight RGBA, computed in Rust.
+//!
+//! Colors are premultiplied or unpremultiplied as needed, with components rounded to the
+//! nearest value. Converting to a format without alpha composites the pixels over black, and
+//! skipped components are filled as if they were an opaque alpha channel, which is what Quartz
+//! does.
+//!
+//! `PixelView` and `PixelViewMut` give access to the rows and pixels of such data without
+//! their padding.
+
+use base::{kCGBitmapByteOrder16Big, kCGBitmapByteOrder16Little, kCGBitmapByteOrder32Big};
+use base::{kCGBitmapByteOrder32Little, kCGBitmapByteOrderDefault, kCGBitmapByteOrderMask};
+use image::{CGBitmapInfo, CGImageAlphaInfo};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+
+/// A layout of RGB pixels with 8 or 16 bits per component, with or without alpha.
+///
+/// Pixels have 3 components with `CGImageAlphaNone` and 4 otherwise, in the order given by the
+/// alpha info, e.g. ARGB for `CGImageAlphaPremultipliedFirst`. The bytes of each 8-bit pixel
+/// are reversed with `kCGBitmapByteOrder32Little`, which makes it BGRA. 16-bit components are
+/// big-endian unless the byte order is `kCGBitmapByteOrder16Little`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PixelFormat {
+    bitmap_info: CGBitmapInfo,
+    bits_per_component: usize,
+}
+
+impl PixelFormat {
+    /// Returns the format, or `None` for alpha-only formats, float components, byte orders that
+    /// don't match the size of components or pixels, and other component sizes.
+    pub fn new(bitmap_info: CGBitmapInfo, bits_per_component: usize) -> Option<PixelFormat> {
+        let byte_order = bitmap_info.bits() & kCGBitmapByteOrderMask;
+        let has_alpha = match bitmap_info.alpha_info() {
+            CGImageAlphaInfo::CGImageAlphaOnly => return None,
+            CGImageAlphaInfo::CGImageAlphaNone => false,
+            _ => true,
+        };
+        let byte_order_matches = match bits_per_component {
+            8 => {
+                byte_order == kCGBitmapByteOrderDefault ||
+                    has_alpha && (byte_order == kCGBitmapByteOrder32Big ||
+                                  byte_order == kCGBitmapByteOrder32Little)
+            }
+            16 => {
+                byte_order == kCGBitmapByteOrderDefault || byte_order == kCGBitmapByteOrder16Big ||
+                    byte_order == kCGBitmapByteOrder16Little
+            }
+            _ => false,
+        };
+        if !byte_order_matches || bitmap_info.has_float_components() {
+            return None;
+        }
+        Some(PixelFormat {
+            bitmap_info: bitmap_info,
+            bits_per_component: bits_per_component,
+        })
+    }
+
+    #[inline]
+    pub fn bitmap_info(self) -> CGBitmapInfo {
+        self.bitmap_info
+    }
+
+    #[inline]
+    pub fn bits_per_component(self) -> usize {
+        self.bits_per_component
+    }
+
+    #[inline]
+    pub fn bytes_per_pixel(self) -> usize {
+        self.components() * self.bits_per_component / 8
+    }
+
+    #[inline]
+    fn components(self) -> usize {
+        if self.alpha() == Alpha::None { 3 } else { 4 }
+    }
+
+    fn alpha(self) -> Alpha {
+        match self.bitmap_info.alpha_info() {
+            CGImageAlphaInfo::CGImageAlphaPremultipliedFirst |
+            CGImageAlphaInfo::CGImageAlphaPremultipliedLast => Alpha::Premultiplied,
+            CGImageAlphaInfo::CGImageAlphaFirst |
+            CGImageAlphaInfo::CGImageAlphaLast => Alpha::Straight,
+            CGImageAlphaInfo::CGImageAlphaNoneSkipFirst |
+            CGImageAlphaInfo::CGImageAlphaNoneSkipLast => Alpha::Skipped,
+            _ => Alpha::None,
+        }
+    }
+
+    /// Returns the index of the alpha or skipped component, in memory order for 8-bit pixels.
+    fn alpha_index(self) -> usize {
+        let first = matches!(self.bitmap_info.alpha_info(),
+                             CGImageAlphaInfo::CGImageAlphaPremultipliedFirst |
+                             CGImageAlphaInfo::CGImageAlphaFirst |
+                             CGImageAlphaInfo::CGImageAlphaNoneSkipFirst);
+        if first != self.is_reversed() { 0 } else { 3 }
+    }
+
+    #[inline]
+    fn is_reversed(self) -> bool {
+        self.bitmap_info.bits() & kCGBitmapByteOrderMask == kCGBitmapByteOrder32Little
+    }
+
+    #[inline]
+    fn max(self) -> u32 {
+        (1 << self.bits_per_component) - 1
+    }
+
+    /// Reads the RGBA components of a pixel.
+    fn read(self, pixel: &[u8]) -> [u32; 4] {
+        let component = |index: usize| {
+            if self.bits_per_component == 8 {
+                pixel[index] as u32
+            } else if self.bitmap_info.bits() & kCGBitmapByteOrderMask ==
+                    kCGBitmapByteOrder16Little {
+                pixel[index * 2] as u32 | (pixel[index * 2 + 1] as u32) << 8
+            } else {
+                (pixel[index * 2] as u32) << 8 | pixel[index * 2 + 1] as u32
+            }
+        };
+        if self.alpha() == Alpha::None {
+            return [component(0), component(1), component(2), self.max()];
+        }
+        let alpha_index = self.alpha_index();
+        let color = if alpha_index == 0 { 1 } else { 0 };
+        let (r, g, b) = if self.is_reversed() {
+            (component(color + 2), component(color + 1), component(color))
+        } else {
+            (component(color), component(color + 1), component(color + 2))
+        };
+        let a = if self.alpha() == Alpha::Skipped { self.max() } else { component(alpha_index) };
+        [r, g, b, a]
+    }
+
+    /// Writes RGBA components, filling skipped components with the maximum value.
+    fn write(self, pixel: &mut [u8], rgba: [u32; 4]) {
+        let little_16 = self.bitmap_info.bits() & kCGBitmapByteOrderMask ==
+            kCGBitmapByteOrder16Little;
+        let mut component = |index: usize, value: u32| {
+            if self.bits_per_component == 8 {
+                pixel[index] = value as u8;
+            } else if little_16 {
+                pixel[index * 2] = value as u8;
+                pixel[index * 2 + 1] = (value >> 8) as u8;
+            } else {
+                pixel[index * 2] = (value >> 8) as u8;
+                pixel[index * 2 + 1] = value as u8;
+            }
+        };
+        if self.alpha() == Alpha::None {
+            for (index, &value) in rgba[..3].iter().enumerate() {
+                component(index, value);
+            }
+            return;
+        }
+        let alpha_index = self.alpha_index();
+        let color = if alpha_index == 0 { 1 } else { 0 };
+        let alpha = if self.alpha() == Alpha::Skipped { self.max() } else { rgba[3] };
+        component(alpha_index, alpha);
+        if self.is_reversed() {
+            component(color, rgba[2]);
+            component(color + 1, rgba[1]);
+            component(color + 2, rgba[0]);
+        } else {
+            component(color, rgba[0]);
+            component(color + 1, rgba[1]);
+            component(color + 2, rgba[2]);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Alpha {
+    None,
+    Skipped,
+    Premultiplied,
+    Straight,
+}
+
+/// A format and the distance between the starts of consecutive rows, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PixelLayout {
+    pub format: PixelFormat,
+    pub bytes_per_row: usize,
+}
+
+impl PixelLayout {
+    #[inline]
+    pub fn new(format: PixelFormat, bytes_per_row: usize) -> PixelLayout {
+        PixelLayout {
+            format: format,
+            bytes_per_row: bytes_per_row,
+        }
+    }
+
+    /// Returns the number of bytes needed for `width` by `height` pixels. The last row doesn't
+    /// need to be padded.
+    #[inline]
+    pub fn required_length(&self, width: usize, height: usize) -> Result<usize, PixelError> {
+        required_length(width, height, self.bytes_per_row, self.format.bytes_per_pixel())
+    }
+
+    #[inline]
+    fn check(&self, length: usize, width: usize, height: usize) -> Result<(), PixelError> {
+        check_length(length, width, height, self.bytes_per_row, self.format.bytes_per_pixel())
+    }
+}
+
+fn required_length(width: usize, height: usize, bytes_per_row: usize, bytes_per_pixel: usize)
+                   -> Result<usize, PixelError> {
+    let row_length = width.checked_mul(bytes_per_pixel).ok_or(PixelError::Overflow)?;
+    if bytes_per_row < row_length {
+        return Err(PixelError::BytesPerRowTooSmall {
+            bytes_per_row: bytes_per_row,
+            minimum: row_length,
+        });
+    }
+    if height == 0 {
+        return Ok(0);
+    }
+    (height - 1).checked_mul(bytes_per_row)
+                .and_then(|length| length.checked_add(row_length))
+                .ok_or(PixelError::Overflow)
+}
+
+fn check_length(length: usize,
+                width: usize,
+                height: usize,
+                bytes_per_row: usize,
+                bytes_per_pixel: usize)
+                -> Result<(), PixelError> {
+    let needed = required_length(width, height, bytes_per_row, bytes_per_pixel)?;
+    if length < needed {
+        return Err(PixelError::BufferTooShort {
+            length: length,
+            needed: needed,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the number of bytes per pixel, if pixels are made of whole bytes.
+fn bytes_per_pixel(bits_per_pixel: usize) -> Result<usize, PixelError> {
+    if bits_per_pixel == 0 || !bits_per_pixel.is_multiple_of(8) {
+        return Err(PixelError::UnalignedPixels { bits_per_pixel: bits_per_pixel });
+    }
+    Ok(bits_per_pixel / 8)
+}
+
+/// The reason pixels could not be converted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelError {
+    /// Rows are too short for the width and pixel format.
+    BytesPerRowTooSmall { bytes_per_row: usize, minimum: usize },
+    /// A buffer is too short for the size and layout of the pixels.
+    BufferTooShort { length: usize, needed: usize },
+    /// The size of a row or of the whole buffer doesn't fit in a `usize`.
+    Overflow,
+    /// `convert_in_place` was given formats with different pixel sizes.
+    PixelSizeMismatch,
+    /// Pixels don't start on byte boundaries, so they can't be accessed one by one.
+    UnalignedPixels { bits_per_pixel: usize },
+    /// The context is not a bitmap context, so it has no pixels to view.
+    NoPixelData,
+}
+
+impl Display for PixelError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            PixelError::BytesPerRowTooSmall { bytes_per_row, minimum } => {
+                write!(formatter, "{} bytes per row instead of at least {}", bytes_per_row, minimum)
+            }
+            PixelError::BufferTooShort { length, needed } => {
+                write!(formatter, "buffer is {} bytes long instead of at least {}", length, needed)
+            }
+            PixelError::Overflow => write!(formatter, "pixel buffer size overflow"),
+            PixelError::PixelSizeMismatch => {
+                write!(formatter, "formats have different pixel sizes")
+            }
+            PixelError::UnalignedPixels { bits_per_pixel } => {
+                write!(formatter, "{} bits per pixel isn't a whole number of bytes", bits_per_pixel)
+            }
+            PixelError::NoPixelData => write!(formatter, "context has no pixel data"),
+        }
+    }
+}
+
+impl Error for PixelError {}
+
+/// Converts `width` by `height` pixels from `src` to `dst`. Bytes of `dst` in the padding of
+/// rows are left untouched.
+pub fn convert(width: usize,
+               height: usize,
+               src: &[u8],
+               src_layout: PixelLayout,
+               dst: &mut [u8],
+               dst_layout: PixelLayout)
+               -> Result<(), PixelError> {
+    src_layout.check(src.len(), width, height)?;
+    dst_layout.check(dst.len(), width, height)?;
+    let (src_format, dst_format) = (src_layout.format, dst_layout.format);
+    let (src_size, dst_size) = (src_format.bytes_per_pixel(), dst_format.bytes_per_pixel());
+    for y in 0..height {
+        let src_row = &src[y * src_layout.bytes_per_row..][..width * src_size];
+        let dst_row = &mut dst[y * dst_layout.bytes_per_row..][..width * dst_size];
+        for (src_pixel, dst_pixel) in src_row.chunks(src_size).zip(dst_row.chunks_mut(dst_size)) {
+            let rgba = convert_pixel(src_format.read(src_pixel), src_format, dst_format);
+            dst_format.write(dst_pixel, rgba);
+        }
+    }
+    Ok(())
+}
+
+/// Converts `width` by `height` pixels of `data` from `layout.format` to `to`, which must have
+/// pixels of the same size.
+pub fn convert_in_place(width: usize,
+                        height: usize,
+                        data: &mut [u8],
+                        layout: PixelLayout,
+                        to: PixelFormat)
+                        -> Result<(), PixelError> {
+    let from = layout.format;
+    if from.bytes_per_pixel() != to.bytes_per_pixel() {
+        return Err(PixelError::PixelSizeMismatch);
+    }
+    layout.check(data.len(), width, height)?;
+    let size = from.bytes_per_pixel();
+    for y in 0..height {
+        let row = &mut data[y * layout.bytes_per_row..][..width * size];
+        for pixel in row.chunks_mut(size) {
+            let rgba = convert_pixel(from.read(pixel), from, to);
+            to.write(pixel, rgba);
+        }
+    }
+    Ok(())
+}
+
+/// Converts RGBA components between the alpha representations and component sizes of two
+/// formats. Alpha is handled at the size of the source, for precision.
+fn convert_pixel(rgba: [u32; 4], from: PixelFormat, to: PixelFormat) -> [u32; 4] {
+    let max = from.max();
+    let (r, g, b, a) = (rgba[0], rgba[1], rgba[2], rgba[3]);
+    let color = match (from.alpha(), to.alpha()) {
+        (Alpha::Straight, Alpha::Premultiplied) |
+        (Alpha::Straight, Alpha::Skipped) |
+        (Alpha::Straight, Alpha::None) => {
+            [premultiply(r, a, max), premultiply(g, a, max), premultiply(b, a, max)]
+        }
+        (Alpha::Premultiplied, Alpha::Straight) => {
+            [unpremultiply(r, a, max), unpremultiply(g, a, max), unpremultiply(b, a, max)]
+        }
+        _ => [r, g, b],
+    };
+    let rgba = [color[0], color[1], color[2], a];
+    match (from.bits_per_component, to.bits_per_component) {
+        (16, 8) => {
+            [reduce_16_to_8(rgba[0]), reduce_16_to_8(rgba[1]), reduce_16_to_8(rgba[2]),
+             reduce_16_to_8(rgba[3])]
+        }
+        (8, 16) => [rgba[0] * 257, rgba[1] * 257, rgba[2] * 257, rgba[3] * 257],
+        _ => rgba,
+    }
+}
+
+/// Returns `component * alpha / max`, rounded to the nearest integer.
+#[inline]
+fn premultiply(component: u32, alpha: u32, max: u32) -> u32 {
+    (component * alpha + max / 2) / max
+}
+
+/// Returns `component * max / alpha`, rounded to the nearest integer and clamped to `max`, or 0
+/// if `alpha` is 0.
+#[inline]
+fn unpremultiply(component: u32, alpha: u32, max: u32) -> u32 {
+    if alpha == 0 {
+        return 0;
+    }
+    ((component * max + alpha / 2) / alpha).min(max)
+}
+
+/// Returns `value * 255 / 65535`, rounded to the nearest integer.
+#[inline]
+fn reduce_16_to_8(value: u32) -> u32 {
+    (value * 255 + 32767) / 65535
+}
+
+/// A view of pixels whose rows may be padded, such as the data of an image.
+///
+/// Rows and pixels are returned without padding: rows are `width * bytes_per_pixel` bytes long
+/// and start every `bytes_per_row` bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct PixelView<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    bytes_per_pixel: usize,
+}
+
+impl<'a> PixelView<'a> {
+    /// Checks that `data` holds `height` rows of `width` pixels, the last one of which doesn't
+    /// need to be padded.
+    pub fn new(data: &'a [u8],
+               width: usize,
+               height: usize,
+               bytes_per_row: usize,
+               bits_per_pixel: usize)
+               -> Result<PixelView<'a>, PixelError> {
+        let bytes_per_pixel = bytes_per_pixel(bits_per_pixel)?;
+        check_length(data.len(), width, height, bytes_per_row, bytes_per_pixel)?;
+        Ok(PixelView {
+            data: data,
+            width: width,
+            height: height,
+            bytes_per_row: bytes_per_row,
+            bytes_per_pixel: bytes_per_pixel,
+        })
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    #[inline]
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_pixel
+    }
+
+    /// Returns row `y`, or `None` if it is out of bounds.
+    pub fn row(&self, y: usize) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        Some(&self.data[y * self.bytes_per_row..][..self.width * self.bytes_per_pixel])
+    }
+
+    /// Returns the rows from top to bottom.
+    #[inline]
+    pub fn rows(&self) -> Rows<'a> {
+        Rows {
+            view: *self,
+            y: 0,
+        }
+    }
+
+    /// Returns the bytes of the pixel at `(x, y)`, or `None` if it is out of bounds.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<&'a [u8]> {
+        if x >= self.width {
+            return None;
+        }
+        self.row(y).map(|row| &row[x * self.bytes_per_pixel..][..self.bytes_per_pixel])
+    }
+
+    /// Returns the rows without padding, one after the other.
+    pub fn to_packed(&self) -> Vec<u8> {
+        let mut packed = Vec::with_capacity(self.width * self.bytes_per_pixel * self.height);
+        for row in self.rows() {
+            packed.extend_from_slice(row);
+        }
+        packed
+    }
+
+    /// Copies the rows without padding into the start of `dst`.
+    pub fn copy_to_packed(&self, dst: &mut [u8]) -> Result<(), PixelError> {
+        let row_length = self.width * self.bytes_per_pixel;
+        check_length(dst.len(), self.width, self.height, row_length, self.bytes_per_pixel)?;
+        for (y, row) in self.rows().enumerate() {
+            dst[y * row_length..][..row_length].copy_from_slice(row);
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the rows of a `PixelView`.
+#[derive(Clone, Debug)]
+pub struct Rows<'a> {
+    view: PixelView<'a>,
+    y: usize,
+}
+
+impl<'a> Iterator for Rows<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let row = self.view.row(self.y)?;
+        self.y += 1;
+        Some(row)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.view.height - self.y;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for Rows<'a> {}
+
+/// A mutable view of pixels whose rows may be padded, such as the data of a bitmap context.
+///
+/// See `PixelView`. The padding of rows is never modified.
+#[derive(Debug)]
+pub struct PixelViewMut<'a> {
+    data: &'a mut [u8],
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    bytes_per_pixel: usize,
+}
+
+impl<'a> PixelViewMut<'a> {
+    /// Checks that `data` holds `height` rows of `width` pixels, the last one of which doesn't
+    /// need to be padded.
+    pub fn new(data: &'a mut [u8],
+               width: usize,
+               height: usize,
+               bytes_per_row: usize,
+               bits_per_pixel: usize)
+               -> Result<PixelViewMut<'a>, PixelError> {
+        let bytes_per_pixel = bytes_per_pixel(bits_per_pixel)?;
+        check_length(data.len(), width, height, bytes_per_row, bytes_per_pixel)?;
+        Ok(PixelViewMut {
+            data: data,
+            width: width,
+            height: height,
+            bytes_per_row: bytes_per_row,
+            bytes_per_pixel: bytes_per_pixel,
+        })
+    }
+
+    /// Returns an immutable view of the same pixels.
+    #[inline]
+    pub fn as_view(&self) -> PixelView<'_> {
+        PixelView {
+            data: self.data,
+            width: self.width,
+            height: self.height,
+            bytes_per_row: self.bytes_per_row,
+            bytes_per_pixel: self.bytes_per_pixel,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    #[inline]
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_pixel
+    }
+
+    /// Returns row `y`, or `None` if it is out of bounds.
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        Some(&mut self.data[y * self.bytes_per_row..][..self.width * self.bytes_per_pixel])
+    }
+
+    /// Returns the rows from top to bottom.
+    #[inline]
+    pub fn rows_mut(&mut self) -> RowsMut<'_> {
+        RowsMut {
+            data: &mut self.data[..],
+            remaining: self.height,
+            bytes_per_row: self.bytes_per_row,
+            row_length: self.width * self.bytes_per_pixel,
+        }
+    }
+
+    /// Returns the bytes of the pixel at `(x, y)`, or `None` if it is out of bounds.
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> Option<&mut [u8]> {
+        if x >= self.width {
+            return None;
+        }
+        let bytes_per_pixel = self.bytes_per_pixel;
+        self.row_mut(y).map(|row| &mut row[x * bytes_per_pixel..][..bytes_per_pixel])
+    }
+
+    /// Copies rows without padding, one after the other at the start of `src`, into the view.
+    pub fn copy_from_packed(&mut self, src: &[u8]) -> Result<(), PixelError> {
+        let row_length = self.width * self.bytes_per_pixel;
+        check_length(src.len(), self.width, self.height, row_length, self.bytes_per_pixel)?;
+        for (y, row) in self.rows_mut().enumerate() {
+            row.copy_from_slice(&src[y * row_length..][..row_length]);
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the rows of a `PixelViewMut`.
+#[derive(Debug)]
+pub struct RowsMut<'a> {
+    data: &'a mut [u8],
+    remaining: usize,
+    bytes_per_row: usize,
+    row_length: usize,
+}
+
+impl<'a> Iterator for RowsMut<'a> {
+    type Item = &'a mut [u8];
+
+    fn next(&mut self) -> Option<&'a mut [u8]> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let data = mem::take(&mut self.data);
+        // The last row doesn't need to be padded.
+        let (row, rest) = if self.remaining == 0 {
+            data.split_at_mut(self.row_length)
+        } else {
+            data.split_at_mut(self.bytes_per_row)
+        };
+        self.data = rest;
+        Some(&mut row[..self.row_length])
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for RowsMut<'a> {}
+
+#[cfg(test)]
+fn format(bits: u32, bits_per_component: usize) -> PixelFormat {
+    PixelFormat::new(CGBitmapInfo::from_bits(bits).unwrap(), bits_per_component).unwrap()
+}
+
+#[test]
+fn pixel_component_arithmetic() {
+    let round = |value: f64| (value + 0.5).floor() as u32;
+    for alpha in 0..256 {
+        for component in 0..256 {
+            let premultiplied = premultiply(component, alpha, 255);
+            assert_eq!(premultiplied, round(component as f64 * alpha as f64 / 255.0));
+            // Unpremultiplying gives back a color that premultiplies to the same value.
+            let straight = unpremultiply(premultiplied, alpha, 255);
+            assert_eq!(premultiply(straight, alpha, 255), premultiplied);
+            if alpha > 0 {
+                let expected = round(component as f64 * 255.0 / alpha as f64).min(255);
+                assert_eq!(unpremultiply(component, alpha, 255), expected);
+            } else {
+                assert_eq!(unpremultiply(component, alpha, 255), 0);
+            }
+        }
+    }
+    for alpha in (0..65536).step_by(257) {
+        assert_eq!(premultiply(65535, alpha, 65535), alpha);
+        assert_eq!(premultiply(alpha, 65535, 65535), alpha);
+        assert_eq!(unpremultiply(alpha, alpha, 65535), if alpha == 0 { 0 } else { 65535 });
+    }
+
+    for value in 0..65536 {
+        assert_eq!(reduce_16_to_8(value), round(value as f64 * 255.0 / 65535.0));
+    }
+    for value in 0..256 {
+        assert_eq!(reduce_16_to_8(value * 257), value);
+    }
+}
+
+#[test]
+fn pixel_format_layouts() {
+    use base::*;
+
+    let alpha_infos = [kCGImageAlphaNone, kCGImageAlphaPremultipliedLast,
+                       kCGImageAlphaPremultipliedFirst, kCGImageAlphaLast, kCGImageAlphaFirst,
+                       kCGImageAlphaNoneSkipLast, kCGImageAlphaNoneSkipFirst];
+    let byte_orders = [kCGBitmapByteOrderDefault, kCGBitmapByteOrder32Big,
+                       kCGBitmapByteOrder32Little, kCGBitmapByteOrder16Big,
+                       kCGBitmapByteOrder16Little];
+    let mut formats = vec![];
+    for &alpha_info in &alpha_infos {
+        for &byte_order in &byte_orders {
+            for &bits_per_component in &[8, 16] {
+                let info = CGBitmapInfo::from_bits(alpha_info | byte_order).unwrap();
+                if let Some(format) = PixelFormat::new(info, bits_per_component) {
+                    formats.push(format);
+                }
+            }
+        }
+    }
+    // 8-bit: 6 formats with alpha or skipped components in 3 byte orders, plus RGB. 16-bit: all
+    // 7 in 3 byte orders.
+    assert_eq!(formats.len(), 6 * 3 + 1 + 7 * 3);
+    assert_eq!(PixelFormat::new(CGBitmapInfo::from_bits(kCGImageAlphaOnly).unwrap(), 8), None);
+    let float = CGBitmapInfo::from_bits(kCGImageAlphaLast | kCGBitmapFloatComponents).unwrap();
+    assert_eq!(PixelFormat::new(float, 16), None);
+
+    // The bytes of an orange, mostly transparent pixel in each format, from the documented
+    // layouts. Its values were chosen so that premultiplication is exact at both sizes. Formats
+    // without alpha hold it composited over black, and converting them gives an opaque pixel.
+    let expected = |format: PixelFormat, opaque: bool| -> Vec<u8> {
+        let (r, g, b, a) = match format.alpha() {
+            _ if opaque => (0x3333, 0x1111, 0, 0xffff),
+            Alpha::Premultiplied => (0x3333, 0x1111, 0, 0x3333),
+            Alpha::Straight => (0xffff, 0x5555, 0, 0x3333),
+            Alpha::Skipped | Alpha::None => (0x3333, 0x1111, 0, 0xffff),
+        };
+        let mut components = match format.bitmap_info().alpha_info() {
+            CGImageAlphaInfo::CGImageAlphaNone => vec![r, g, b],
+            CGImageAlphaInfo::CGImageAlphaPremultipliedFirst |
+            CGImageAlphaInfo::CGImageAlphaFirst |
+            CGImageAlphaInfo::CGImageAlphaNoneSkipFirst => vec![a, r, g, b],
+            _ => vec![r, g, b, a],
+        };
+        let byte_order = format.bitmap_info().bits() & kCGBitmapByteOrderMask;
+        if byte_order == kCGBitmapByteOrder32Little {
+            components.reverse();
+        }
+        components.iter().flat_map(|&value: &u32| {
+            match (format.bits_per_component(), byte_order == kCGBitmapByteOrder16Little) {
+                (8, _) => vec![(value >> 8) as u8],
+                (_, true) => vec![value as u8, (value >> 8) as u8],
+                (_, false) => vec![(value >> 8) as u8, value as u8],
+            }
+        }).collect()
+    };
+
+    // Every conversion from every format, applied to that pixel.
+    for &from in &formats {
+        let src = expected(from, false);
+        let opaque = from.alpha() == Alpha::None || from.alpha() == Alpha::Skipped;
+        assert_eq!(src.len(), from.bytes_per_pixel());
+        for &to in &formats {
+            let mut dst = vec![0; to.bytes_per_pixel()];
+            let (src_layout, dst_layout) = (PixelLayout::new(from, src.len()),
+                                            PixelLayout::new(to, dst.len()));
+            convert(1, 1, &src, src_layout, &mut dst, dst_layout).unwrap();
+            assert_eq!(dst, expected(to, opaque), "{:?} to {:?}", from, to);
+        }
+    }
+}
+
+#[test]
+fn pixel_conversion_rows() {
+    use base::*;
+
+    let bgra = format(kCGImageAlphaPremultipliedFirst | kCGBitmapByteOrder32Little, 8);
+    let rgba = format(kCGImageAlphaLast, 8);
+    let xrgb = format(kCGImageAlphaNoneSkipFirst, 8);
+    let rgba_16 = format(kCGImageAlphaPremultipliedLast | kCGBitmapByteOrder16Little, 16);
+
+    // Two rows of two pixels, padded to 12 bytes.
+    let src = [0, 0, 51, 51, 10, 20, 30, 255, 0xee, 0xee, 0xee, 0xee,
+               0, 0, 0, 0, 255, 255, 255, 255, 0xee, 0xee, 0xee, 0xee];
+    let mut dst = [0xaa; 20];
+    convert(2, 2, &src, PixelLayout::new(bgra, 12), &mut dst, PixelLayout::new(rgba, 10))
+        .unwrap();
+    assert_eq!(dst, [255, 0, 0, 51, 30, 20, 10, 255, 0xaa, 0xaa,
+                     0, 0, 0, 0, 255, 255, 255, 255, 0xaa, 0xaa]);
+
+    // The last row doesn't need padding.
+    assert_eq!(PixelLayout::new(bgra, 12).required_length(2, 2), Ok(20));
+    let mut short = [0; 17];
+    assert_eq!(convert(2, 2, &src, PixelLayout::new(bgra, 12), &mut short,
+                       PixelLayout::new(rgba, 10)),
+               Err(PixelError::BufferTooShort { length: 17, needed: 18 }));
+    assert_eq!(convert(2, 2, &src, PixelLayout::new(bgra, 7), &mut dst,
+                       PixelLayout::new(rgba, 10)),
+               Err(PixelError::BytesPerRowTooSmall { bytes_per_row: 7, minimum: 8 }));
+    assert_eq!(PixelLayout::new(bgra, usize::MAX).required_length(1, 3),
+               Err(PixelError::Overflow));
+    assert_eq!(PixelLayout::new(bgra, 0).required_length(0, 5), Ok(0));
+
+    // Filling the skipped component of XRGB data in place, and 16-bit reduction.
+    let mut data = [0, 1, 2, 3, 9, 4, 5, 6, 9, 9];
+    convert_in_place(2, 1, &mut data, PixelLayout::new(xrgb, 10), bgra).unwrap();
+    assert_eq!(data, [3, 2, 1, 255, 6, 5, 4, 255, 9, 9]);
+    assert_eq!(convert_in_place(2, 1, &mut data, PixelLayout::new(xrgb, 10), rgba_16),
+               Err(PixelError::PixelSizeMismatch));
+    let wide = [0x80, 0x80, 0, 0, 0, 0, 0x80, 0x80];
+    let mut narrow = [0; 4];
+    convert(1, 1, &wide, PixelLayout::new(rgba_16, 8), &mut narrow, PixelLayout::new(rgba, 4))
+        .unwrap();
+    assert_eq!(narrow, [255, 0, 0, 128]);
+}
+
+#[test]
+fn pixel_views() {
+    // Three rows of two 3-byte pixels, padded to 8 bytes except for the last one.
+    let mut data: Vec<u8> = (0..22).collect();
+    {
+        let view = PixelView::new(&data, 2, 3, 8, 24).unwrap();
+        assert_eq!(view.bytes_per_pixel(), 3);
+        assert_eq!(view.row(1), Some(&[8, 9, 10, 11, 12, 13][..]));
+        assert_eq!(view.row(3), None);
+        assert_eq!(view.pixel(1, 2), Some(&[19, 20, 21][..]));
+        assert_eq!(view.pixel(2, 0), None);
+        assert_eq!(view.pixel(0, 3), None);
+        let rows = view.rows();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows.map(|row| row[0]).collect::<Vec<_>>(), vec![0, 8, 16]);
+        let packed = view.to_packed();
+        assert_eq!(packed, vec![0, 1, 2, 3, 4, 5, 8, 9, 10, 11, 12, 13, 16, 17, 18, 19, 20, 21]);
+        let mut copy = vec![0; 18];
+        view.copy_to_packed(&mut copy).unwrap();
+        assert_eq!(copy, packed);
+        assert_eq!(view.copy_to_packed(&mut [0; 17]),
+                   Err(PixelError::BufferTooShort { length: 17, needed: 18 }));
+    }
+
+    {
+        let mut view = PixelViewMut::new(&mut data, 2, 3, 8, 24).unwrap();
+        view.pixel_mut(1, 1).unwrap().copy_from_slice(&[100, 101, 102]);
+        assert!(view.pixel_mut(0, 3).is_none());
+        for row in view.rows_mut() {
+            row[0] = 200;
+        }
+        assert_eq!(view.as_view().row(1), Some(&[200, 9, 10, 100, 101, 102][..]));
+        let packed: Vec<u8> = (50..68).collect();
+        view.copy_from_packed(&packed).unwrap();
+        assert_eq!(view.as_view().to_packed(), packed);
+        assert_eq!(view.copy_from_packed(&packed[1..]),
+                   Err(PixelError::BufferTooShort { length: 17, needed: 18 }));
+    }
+    // The padding is untouched.
+    assert_eq!(&data[6..8], &[6, 7]);
+    assert_eq!(&data[14..16], &[14, 15]);
+
+    assert_eq!(PixelView::new(&data, 2, 3, 8, 12).unwrap_err(),
+               PixelError::UnalignedPixels { bits_per_pixel: 12 });
+    assert_eq!(PixelView::new(&data, 3, 3, 8, 24).unwrap_err(),
+               PixelError::BytesPerRowTooSmall { bytes_per_row: 8, minimum: 9 });
+    assert_eq!(PixelView::new(&data[..21], 2, 3, 8, 24).unwrap_err(),
+               PixelError::BufferTooShort { length: 21, needed: 22 });
+    let empty = PixelViewMut::new(&mut [], 0, 2, 0, 32).unwrap().rows_mut().count();
+    assert_eq!(empty, 2);
+}
@@ -13,6 +13,7 @@
 
 use geometry::CGRect;
 use libc::{c_int, c_uint};
+use region::Region;
 use std::ptr;
 
 pub struct CGSRegion {
@@ -51,6 +52,12 @@ impl CGSRegion {
             }
         }
     }
+
+    /// Creates a window server region covering the same area as `region`.
+    #[inline]
+    pub fn from_region(region: &Region) -> CGSRegion {
+        CGSRegion::from_rects(&region.rects().collect::<Vec<_>>())
+    }
 }
 
 /// This should always be memory-safe; the window server rejects any invalid surface IDs.
@@ -0,0 +1,301 @@
+// Copyright 2018 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Areas made of rectangles, computed in Rust.
+//!
+//! A `Region` can be turned into a window server region with `CGSRegion::from_region`, e.g. to
+//! set a window shape, but is otherwise independent of the framework.
+
+use base::CGFloat;
+use geometry::{CG_RECT_NULL, CGPoint, CGRect, CGSize};
+
+/// A set of points in the plane, stored as horizontal bands of disjoint spans.
+///
+/// The representation is canonical: bands are sorted top to bottom, don't overlap, have at
+/// least one span, and vertically adjacent bands with the same spans are merged. Spans within
+/// a band are sorted, disjoint and non-adjacent. Two regions covering the same area are
+/// therefore equal.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Region {
+    bands: Vec<Band>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Band {
+    top: CGFloat,
+    bottom: CGFloat,
+    spans: Vec<(CGFloat, CGFloat)>,
+}
+
+impl Region {
+    /// Returns an empty region.
+    #[inline]
+    pub fn new() -> Region {
+        Region {
+            bands: vec![],
+        }
+    }
+
+    /// Returns the region covered by `rect`. Null, empty and non-finite rectangles produce an
+    /// empty region.
+    pub fn from_rect(rect: &CGRect) -> Region {
+        let finite = rect.origin.x.is_finite() && rect.origin.y.is_finite() &&
+            rect.size.width.is_finite() && rect.size.height.is_finite();
+        if !finite || rect.is_empty() {
+            return Region::new();
+        }
+
+        let rect = rect.standardize();
+        Region {
+            bands: vec![Band {
+                top: rect.origin.y,
+                bottom: rect.origin.y + rect.size.height,
+                spans: vec![(rect.origin.x, rect.origin.x + rect.size.width)],
+            }],
+        }
+    }
+
+    /// Returns the union of all of `rects`, skipping those that are not finite.
+    pub fn from_rects(rects: &[CGRect]) -> Region {
+        rects.iter().fold(Region::new(), |region, rect| region.union(&Region::from_rect(rect)))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bands.is_empty()
+    }
+
+    /// Returns the smallest rectangle containing the region, or the null rectangle if the region
+    /// is empty.
+    pub fn bounding_box(&self) -> CGRect {
+        let (first, last) = match (self.bands.first(), self.bands.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return CG_RECT_NULL,
+        };
+
+        let min_x = self.bands.iter()
+                              .map(|band| band.spans[0].0)
+                              .fold(CGFloat::INFINITY, CGFloat::min);
+        let max_x = self.bands.iter()
+                              .map(|band| band.spans[band.spans.len() - 1].1)
+                              .fold(CGFloat::NEG_INFINITY, CGFloat::max);
+        CGRect::new(&CGPoint::new(min_x, first.top),
+                    &CGSize::new(max_x - min_x, last.bottom - first.top))
+    }
+
+    /// Returns true if `point` lies inside the region. As with `CGRect::contains_point`, the
+    /// minimum edges of each rectangle are inclusive and the maximum edges exclusive.
+    pub fn contains_point(&self, point: &CGPoint) -> bool {
+        self.bands.iter().any(|band| {
+            point.y >= band.top && point.y < band.bottom &&
+                band.spans.iter().any(|&(left, right)| point.x >= left && point.x < right)
+        })
+    }
+
+    /// Returns the region moved by `(dx, dy)`, or an empty region if either is not finite.
+    pub fn translate(&self, dx: CGFloat, dy: CGFloat) -> Region {
+        if !dx.is_finite() || !dy.is_finite() {
+            return Region::new();
+        }
+        Region {
+            bands: self.bands.iter().map(|band| {
+                Band {
+                    top: band.top + dy,
+                    bottom: band.bottom + dy,
+                    spans: band.spans.iter()
+                                     .map(|&(left, right)| (left + dx, right + dx))
+                                     .collect(),
+                }
+            }).collect(),
+        }
+    }
+
+    #[inline]
+    pub fn union(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a || b)
+    }
+
+    #[inline]
+    pub fn intersection(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a && b)
+    }
+
+    /// Returns the part of this region that is not covered by `other`.
+    #[inline]
+    pub fn subtract(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a && !b)
+    }
+
+    /// Returns the parts covered by exactly one of the two regions.
+    #[inline]
+    pub fn xor(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a != b)
+    }
+
+    /// Returns the rectangles making up the region, one per span, from top to bottom and left
+    /// to right. The rectangles don't overlap.
+    #[inline]
+    pub fn rects(&self) -> Rects<'_> {
+        Rects {
+            bands: &self.bands,
+            band: 0,
+            span: 0,
+        }
+    }
+
+    /// Computes the region containing the points for which `op` returns true, given whether the
+    /// point is in `self` and in `other`.
+    fn combine<F>(&self, other: &Region, op: F) -> Region where F: Fn(bool, bool) -> bool {
+        let mut edges: Vec<CGFloat> = self.bands.iter().chain(other.bands.iter())
+                                                .flat_map(|band| vec![band.top, band.bottom])
+                                                .collect();
+        sort_and_dedup(&mut edges);
+
+        let mut result = Region::new();
+        let (mut a, mut b) = (0, 0);
+        for window in edges.windows(2) {
+            let (top, bottom) = (window[0], window[1]);
+            while a < self.bands.len() && self.bands[a].bottom <= top {
+                a += 1;
+            }
+            while b < other.bands.len() && other.bands[b].bottom <= top {
+                b += 1;
+            }
+
+            // Every edge of both regions is in `edges`, so a band either covers all of
+            // `top..bottom` or none of it.
+            let spans_a = spans_at(&self.bands, a, top);
+            let spans_b = spans_at(&other.bands, b, top);
+            let spans = combine_spans(spans_a, spans_b, &op);
+            result.push_band(top, bottom, spans);
+        }
+        result
+    }
+
+    /// Appends a band below the existing ones, merging it into the last band if possible.
+    fn push_band(&mut self, top: CGFloat, bottom: CGFloat, spans: Vec<(CGFloat, CGFloat)>) {
+        if spans.is_empty() {
+            return;
+        }
+        if let Some(last) = self.bands.last_mut() {
+            if last.bottom == top && last.spans == spans {
+                last.bottom = bottom;
+                return;
+            }
+        }
+        self.bands.push(Band {
+            top: top,
+            bottom: bottom,
+            spans: spans,
+        });
+    }
+}
+
+/// Sorts and deduplicates the edges of bands or spans, which are never NaN since regions only
+/// hold finite coordinates.
+fn sort_and_dedup(values: &mut Vec<CGFloat>) {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values.dedup();
+}
+
+fn spans_at(bands: &[Band], index: usize, y: CGFloat) -> &[(CGFloat, CGFloat)] {
+    match bands.get(index) {
+        Some(band) if band.top <= y => &band.spans,
+        _ => &[],
+    }
+}
+
+fn combine_spans<F>(a: &[(CGFloat, CGFloat)], b: &[(CGFloat, CGFloat)], op: &F)
+                    -> Vec<(CGFloat, CGFloat)>
+                    where F: Fn(bool, bool) -> bool {
+    let mut edges: Vec<CGFloat> = a.iter().chain(b.iter())
+                                   .flat_map(|&(left, right)| vec![left, right])
+                                   .collect();
+    sort_and_dedup(&mut edges);
+
+    let inside = |spans: &[(CGFloat, CGFloat)], x: CGFloat| {
+        spans.iter().any(|&(left, right)| x >= left && x < right)
+    };
+
+    let mut result: Vec<(CGFloat, CGFloat)> = vec![];
+    for window in edges.windows(2) {
+        let (left, right) = (window[0], window[1]);
+        if !op(inside(a, left), inside(b, left)) {
+            continue;
+        }
+        match result.last_mut() {
+            Some(last) if last.1 == left => last.1 = right,
+            _ => result.push((left, right)),
+        }
+    }
+    result
+}
+
+/// An iterator over the rectangles of a `Region`.
+pub struct Rects<'a> {
+    bands: &'a [Band],
+    band: usize,
+    span: usize,
+}
+
+impl<'a> Iterator for Rects<'a> {
+    type Item = CGRect;
+
+    fn next(&mut self) -> Option<CGRect> {
+        let band = self.bands.get(self.band)?;
+        let (left, right) = band.spans[self.span];
+        self.span += 1;
+        if self.span == band.spans.len() {
+            self.band += 1;
+            self.span = 0;
+        }
+        Some(CGRect::new(&CGPoint::new(left, band.top),
+                         &CGSize::new(right - left, band.bottom - band.top)))
+    }
+}
+
+#[test]
+fn region_operations() {
+    let rect = |x, y, width, height| CGRect::new(&CGPoint::new(x, y), &CGSize::new(width, height));
+    let a = Region::from_rect(&rect(0.0, 0.0, 10.0, 10.0));
+    let b = Region::from_rect(&rect(5.0, 5.0, 10.0, 10.0));
+
+    let union = a.union(&b);
+    assert_eq!(union.rects().collect::<Vec<_>>(), vec![
+        rect(0.0, 0.0, 10.0, 5.0),
+        rect(0.0, 5.0, 15.0, 5.0),
+        rect(5.0, 10.0, 10.0, 5.0),
+    ]);
+    assert_eq!(union, b.union(&a));
+    assert_eq!(union.bounding_box(), rect(0.0, 0.0, 15.0, 15.0));
+
+    assert_eq!(a.intersection(&b).rects().collect::<Vec<_>>(), vec![rect(5.0, 5.0, 5.0, 5.0)]);
+    assert!(a.intersection(&b.translate(20.0, 0.0)).is_empty());
+
+    let hole = a.subtract(&Region::from_rect(&rect(3.0, 3.0, 4.0, 4.0)));
+    assert_eq!(hole.rects().count(), 4);
+    assert!(hole.contains_point(&CGPoint::new(1.0, 5.0)));
+    assert!(!hole.contains_point(&CGPoint::new(5.0, 5.0)));
+    assert!(!hole.contains_point(&CGPoint::new(10.0, 5.0)));
+    assert_eq!(hole.union(&Region::from_rect(&rect(3.0, 3.0, 4.0, 4.0))), a);
+
+    assert_eq!(a.xor(&b), union.subtract(&a.intersection(&b)));
+    assert_eq!(Region::from_rects(&[rect(0.0, 0.0, 5.0, 10.0), rect(5.0, 0.0, 5.0, 10.0)]), a);
+
+    let moved = a.translate(2.0, -3.0);
+    assert_eq!(moved.bounding_box(), rect(2.0, -3.0, 10.0, 10.0));
+    assert!(Region::from_rect(&CG_RECT_NULL).bounding_box().is_null());
+
+    // Rectangles with NaN or infinite coordinates are skipped rather than breaking the bands.
+    let nan = CGFloat::NAN;
+    assert!(Region::from_rect(&rect(nan, 0.0, 1.0, 1.0)).is_empty());
+    assert!(Region::from_rect(&rect(0.0, 0.0, CGFloat::INFINITY, 1.0)).is_empty());
+    assert_eq!(Region::from_rects(&[rect(0.0, 0.0, 10.0, 10.0), rect(0.0, nan, nan, 1.0)]), a);
+    assert!(a.translate(nan, 0.0).is_empty());
+}
@@ -0,0 +1,496 @@
+// Copyright 2018 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Conversion between paths and SVG path data, i.e. the `d` attribute of `<path>` elements.
+//!
+//! Parsing follows the grammar of SVG 1.1 and supports every command. Elliptical arcs are
+//! converted to cubic curves, and horizontal, vertical and smooth commands to their explicit
+//! equivalents, so only the element types of `CGPath` remain.
+
+use base::CGFloat;
+use geometry::{CGPoint, CGSize};
+use path::{CGMutablePath, CGPath, CGPathRef, PathElement};
+use std::error::Error;
+use std::f64::consts::PI as PI_F64;
+use std::fmt::{self, Display, Formatter, Write};
+
+const PI: CGFloat = PI_F64 as CGFloat;
+
+/// The reason SVG path data could not be parsed, with the byte offset in the data at which it
+/// was found.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SvgPathError {
+    /// The data has commands but doesn't start with a move command.
+    MissingMoveTo,
+    /// A character that isn't a command letter was found where a command was expected.
+    UnexpectedCharacter { offset: usize, character: char },
+    /// A command is missing some of its numbers, or one of them is malformed.
+    InvalidNumber { offset: usize },
+    /// An arc flag is not `0` or `1`.
+    InvalidFlag { offset: usize },
+}
+
+impl Display for SvgPathError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            SvgPathError::MissingMoveTo => {
+                write!(formatter, "path data must start with a move command")
+            }
+            SvgPathError::UnexpectedCharacter { offset, character } => {
+                write!(formatter, "unexpected character {:?} at offset {}", character, offset)
+            }
+            SvgPathError::InvalidNumber { offset } => {
+                write!(formatter, "expected a number at offset {}", offset)
+            }
+            SvgPathError::InvalidFlag { offset } => {
+                write!(formatter, "expected an arc flag at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl Error for SvgPathError {}
+
+impl CGPath {
+    /// Parses SVG path data, such as `"M 0 0 L 10 0 A 5 5 0 0 1 10 10 z"`.
+    ///
+    /// Empty data gives an empty path. Any error makes the whole parse fail, rather than keeping
+    /// the path up to the error as SVG renderers do.
+    pub fn from_svg_path_data(data: &str) -> Result<CGPath, SvgPathError> {
+        let mut parser = Parser {
+            data: data.as_bytes(),
+            offset: 0,
+        };
+        let mut path = CGMutablePath::new();
+        let mut state = State {
+            current: CGPoint::new(0.0, 0.0),
+            subpath_start: CGPoint::new(0.0, 0.0),
+            last_control: None,
+        };
+
+        let mut command = None;
+        loop {
+            parser.skip_separators();
+            let letter = match parser.peek() {
+                None => break,
+                Some(c) if c.is_ascii_alphabetic() => {
+                    parser.offset += 1;
+                    c
+                }
+                // Numbers after a command repeat it, except that a move is followed by lines.
+                Some(c) => match command {
+                    Some(b'M') => b'L',
+                    Some(b'm') => b'l',
+                    Some(previous) if previous != b'Z' && previous != b'z' => previous,
+                    _ => {
+                        return Err(SvgPathError::UnexpectedCharacter {
+                            offset: parser.offset,
+                            character: data[parser.offset..].chars().next().unwrap_or(c as char),
+                        })
+                    }
+                },
+            };
+            if command.is_none() && b"LlHhVvCcSsQqTtAaZz".contains(&letter) {
+                return Err(SvgPathError::MissingMoveTo);
+            }
+            state.apply(letter, &mut parser, &mut path, data)?;
+            command = Some(letter);
+        }
+        Ok(path.into_path())
+    }
+}
+
+impl CGPathRef {
+    /// Serializes the path as SVG path data, using only absolute `M`, `L`, `Q`, `C` and `Z`
+    /// commands.
+    ///
+    /// Coordinates are written with the shortest representation that parses back to the same
+    /// value, so `CGPath::from_svg_path_data` gives back the same elements as long as they are
+    /// finite.
+    pub fn to_svg_path_data(&self) -> String {
+        let mut data = String::new();
+        for element in self.elements() {
+            if !data.is_empty() {
+                data.push(' ');
+            }
+            // Writing to a `String` can't fail.
+            let _ = match element {
+                PathElement::MoveTo(p) => write!(data, "M {} {}", p.x, p.y),
+                PathElement::LineTo(p) => write!(data, "L {} {}", p.x, p.y),
+                PathElement::QuadTo(c, p) => write!(data, "Q {} {} {} {}", c.x, c.y, p.x, p.y),
+                PathElement::CubicTo(c1, c2, p) => {
+                    write!(data, "C {} {} {} {} {} {}", c1.x, c1.y, c2.x, c2.y, p.x, p.y)
+                }
+                PathElement::Close => write!(data, "Z"),
+            };
+        }
+        data
+    }
+}
+
+/// The position in the path data.
+struct Parser<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    #[inline]
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.offset).cloned()
+    }
+
+    /// Skips whitespace and at most one comma.
+    fn skip_separators(&mut self) {
+        self.skip_whitespace();
+        if self.peek() == Some(b',') {
+            self.offset += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| b" \t\n\r\x0c".contains(&c)) {
+            self.offset += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<CGFloat, SvgPathError> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let digits = |parser: &mut Parser| {
+            let digits_start = parser.offset;
+            while let Some(b'0'..=b'9') = parser.peek() {
+                parser.offset += 1;
+            }
+            parser.offset > digits_start
+        };
+
+        if let Some(b'+') | Some(b'-') = self.peek() {
+            self.offset += 1;
+        }
+        let mut mantissa = digits(self);
+        if self.peek() == Some(b'.') {
+            self.offset += 1;
+            mantissa |= digits(self);
+        }
+        if !mantissa {
+            self.offset = start;
+            return Err(SvgPathError::InvalidNumber { offset: start });
+        }
+
+        // An `e` not followed by an exponent belongs to the next token.
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            let before_exponent = self.offset;
+            self.offset += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.offset += 1;
+            }
+            if !digits(self) {
+                self.offset = before_exponent;
+            }
+        }
+
+        let text = ::std::str::from_utf8(&self.data[start..self.offset]).unwrap();
+        let number = text.parse::<CGFloat>()
+                         .map_err(|_| SvgPathError::InvalidNumber { offset: start })?;
+        self.skip_separators();
+        Ok(number)
+    }
+
+    fn point(&mut self) -> Result<CGPoint, SvgPathError> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(CGPoint::new(x, y))
+    }
+
+    fn flag(&mut self) -> Result<bool, SvgPathError> {
+        self.skip_whitespace();
+        let flag = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(SvgPathError::InvalidFlag { offset: self.offset }),
+        };
+        self.offset += 1;
+        self.skip_separators();
+        Ok(flag)
+    }
+}
+
+/// What earlier commands left behind for later ones.
+struct State {
+    current: CGPoint,
+    subpath_start: CGPoint,
+    /// The last control point of the previous command and whether it was a cubic curve, for
+    /// smooth curve commands to reflect.
+    last_control: Option<(CGPoint, bool)>,
+}
+
+impl State {
+    fn apply(&mut self,
+             letter: u8,
+             parser: &mut Parser,
+             path: &mut CGMutablePath,
+             data: &str)
+             -> Result<(), SvgPathError> {
+        let relative = letter.is_ascii_lowercase();
+        let origin = if relative { self.current } else { CGPoint::new(0.0, 0.0) };
+        let absolute = |p: CGPoint| CGPoint::new(origin.x + p.x, origin.y + p.y);
+        let mut last_control = None;
+
+        let command = letter.to_ascii_uppercase();
+        match command {
+            b'M' => {
+                let p = absolute(parser.point()?);
+                path.move_to(p);
+                self.subpath_start = p;
+                self.current = p;
+            }
+            b'L' => {
+                let p = absolute(parser.point()?);
+                path.line_to(p);
+                self.current = p;
+            }
+            b'H' => {
+                let x = parser.number()? + origin.x;
+                let p = CGPoint::new(x, self.current.y);
+                path.line_to(p);
+                self.current = p;
+            }
+            b'V' => {
+                let y = parser.number()? + origin.y;
+                let p = CGPoint::new(self.current.x, y);
+                path.line_to(p);
+                self.current = p;
+            }
+            b'C' | b'S' => {
+                let c1 = if command == b'C' {
+                    absolute(parser.point()?)
+                } else {
+                    self.reflected_control(true)
+                };
+                let c2 = absolute(parser.point()?);
+                let p = absolute(parser.point()?);
+                path.curve_to(c1, c2, p);
+                self.current = p;
+                last_control = Some((c2, true));
+            }
+            b'Q' | b'T' => {
+                let c = if command == b'Q' {
+                    absolute(parser.point()?)
+                } else {
+                    self.reflected_control(false)
+                };
+                let p = absolute(parser.point()?);
+                path.quad_to(c, p);
+                self.current = p;
+                last_control = Some((c, false));
+            }
+            b'A' => {
+                let rx = parser.number()?;
+                let ry = parser.number()?;
+                let rotation = parser.number()?;
+                let large_arc = parser.flag()?;
+                let sweep = parser.flag()?;
+                let p = absolute(parser.point()?);
+                add_svg_arc(path, self.current, CGSize::new(rx, ry), rotation, large_arc, sweep, p);
+                self.current = p;
+            }
+            b'Z' => {
+                path.close();
+                self.current = self.subpath_start;
+            }
+            _ => {
+                let offset = parser.offset - 1;
+                return Err(SvgPathError::UnexpectedCharacter {
+                    offset: offset,
+                    character: data[offset..].chars().next().unwrap(),
+                });
+            }
+        }
+        self.last_control = last_control;
+        Ok(())
+    }
+
+    /// Returns the first control point of a smooth curve: the reflection of the previous
+    /// curve's last control point if it was of the same kind, or else the current point.
+    fn reflected_control(&self, cubic: bool) -> CGPoint {
+        match self.last_control {
+            Some((control, was_cubic)) if was_cubic == cubic => {
+                CGPoint::new(2.0 * self.current.x - control.x, 2.0 * self.current.y - control.y)
+            }
+            _ => self.current,
+        }
+    }
+}
+
+/// Adds an SVG elliptical arc with the radii `radii` from `from` to `to` as cubic curves,
+/// following the conversion from endpoint to center parameterization in the SVG implementation
+/// notes.
+fn add_svg_arc(path: &mut CGMutablePath,
+               from: CGPoint,
+               radii: CGSize,
+               rotation_degrees: CGFloat,
+               large_arc: bool,
+               sweep: bool,
+               to: CGPoint) {
+    if from == to {
+        return;
+    }
+    let (mut rx, mut ry) = (radii.width.abs(), radii.height.abs());
+    if rx == 0.0 || ry == 0.0 {
+        return path.line_to(to);
+    }
+
+    let phi = rotation_degrees * PI / 180.0;
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let (dx, dy) = ((from.x - to.x) * 0.5, (from.y - to.y) * 0.5);
+    let x1 = cos_phi * dx + sin_phi * dy;
+    let y1 = -sin_phi * dx + cos_phi * dy;
+
+    // Scale up radii that are too small to reach the end point.
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        coefficient = -coefficient;
+    }
+    let cx1 = coefficient * rx * y1 / ry;
+    let cy1 = -coefficient * ry * x1 / rx;
+    let center = CGPoint::new(cos_phi * cx1 - sin_phi * cy1 + (from.x + to.x) * 0.5,
+                              sin_phi * cx1 + cos_phi * cy1 + (from.y + to.y) * 0.5);
+
+    let angle = |ux: CGFloat, uy: CGFloat| uy.atan2(ux);
+    let start_angle = angle((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let end_angle = angle((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    let mut sweep_angle = end_angle - start_angle;
+    if sweep && sweep_angle < 0.0 {
+        sweep_angle += 2.0 * PI;
+    } else if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= 2.0 * PI;
+    }
+
+    // Each piece of at most a quarter turn of the unit circle becomes one cubic, which is then
+    // scaled and rotated onto the ellipse.
+    let on_ellipse = |x: CGFloat, y: CGFloat| {
+        CGPoint::new(center.x + cos_phi * rx * x - sin_phi * ry * y,
+                     center.y + sin_phi * rx * x + cos_phi * ry * y)
+    };
+    let segments = (sweep_angle.abs() / (PI * 0.5) - 1e-9).ceil().max(1.0) as usize;
+    let step = sweep_angle / segments as CGFloat;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    for i in 0..segments {
+        let a0 = start_angle + step * i as CGFloat;
+        let a1 = a0 + step;
+        let (sin0, cos0) = a0.sin_cos();
+        let (sin1, cos1) = a1.sin_cos();
+        let end = if i + 1 == segments { to } else { on_ellipse(cos1, sin1) };
+        path.curve_to(on_ellipse(cos0 - k * sin0, sin0 + k * cos0),
+                      on_ellipse(cos1 + k * sin1, sin1 - k * cos1),
+                      end);
+    }
+}
+
+#[test]
+fn svg_path_round_trip() {
+    use path::CGPathElementType;
+
+    let mut path = CGMutablePath::new();
+    path.move_to(CGPoint::new(0.5, -1.0));
+    path.line_to(CGPoint::new(10.0, 0.1));
+    path.quad_to(CGPoint::new(1e-7, 3.0), CGPoint::new(-2.5, 4.0));
+    path.curve_to(CGPoint::new(1.0, 2.0), CGPoint::new(3.0, 4.0), CGPoint::new(1234567.0, 6.0));
+    path.close();
+    let path = path.into_path();
+
+    // One element of each type.
+    let types: Vec<CGPathElementType> = path.elements().iter().map(|element| match *element {
+        PathElement::MoveTo(_) => CGPathElementType::MoveToPoint,
+        PathElement::LineTo(_) => CGPathElementType::AddLineToPoint,
+        PathElement::QuadTo(..) => CGPathElementType::AddQuadCurveToPoint,
+        PathElement::CubicTo(..) => CGPathElementType::AddCurveToPoint,
+        PathElement::Close => CGPathElementType::CloseSubpath,
+    }).collect();
+    assert_eq!(types, vec![CGPathElementType::MoveToPoint,
+                           CGPathElementType::AddLineToPoint,
+                           CGPathElementType::AddQuadCurveToPoint,
+                           CGPathElementType::AddCurveToPoint,
+                           CGPathElementType::CloseSubpath]);
+
+    let data = path.to_svg_path_data();
+    assert_eq!(data, "M 0.5 -1 L 10 0.1 Q 0.0000001 3 -2.5 4 C 1 2 3 4 1234567 6 Z");
+    assert_eq!(CGPath::from_svg_path_data(&data).unwrap().elements(), path.elements());
+    assert_eq!(CGPath::from_svg_path_data("").unwrap().elements(), vec![]);
+    assert_eq!(CGMutablePath::new().to_svg_path_data(), "");
+}
+
+#[test]
+fn svg_path_commands() {
+    use geometry::{ApproxEq, CGRect};
+
+    let elements = |data: &str| CGPath::from_svg_path_data(data).unwrap().elements();
+    let p = CGPoint::new;
+
+    // Relative commands, implicit lines after a move, and compact number syntax.
+    assert_eq!(elements("m1,1 2,0l0-2h-1.5.5V3e0z"), vec![
+        PathElement::MoveTo(p(1.0, 1.0)),
+        PathElement::LineTo(p(3.0, 1.0)),
+        PathElement::LineTo(p(3.0, -1.0)),
+        PathElement::LineTo(p(1.5, -1.0)),
+        PathElement::LineTo(p(2.0, -1.0)),
+        PathElement::LineTo(p(2.0, 3.0)),
+        PathElement::Close,
+    ]);
+
+    // Smooth curves reflect the previous control point only after a curve of the same kind.
+    assert_eq!(elements("M0 0C0 1 1 1 1 0S2-1 2 0Q3 1 4 0T6 0 8 0M0 0S1 1 2 0"), vec![
+        PathElement::MoveTo(p(0.0, 0.0)),
+        PathElement::CubicTo(p(0.0, 1.0), p(1.0, 1.0), p(1.0, 0.0)),
+        PathElement::CubicTo(p(1.0, -1.0), p(2.0, -1.0), p(2.0, 0.0)),
+        PathElement::QuadTo(p(3.0, 1.0), p(4.0, 0.0)),
+        PathElement::QuadTo(p(5.0, -1.0), p(6.0, 0.0)),
+        PathElement::QuadTo(p(7.0, 1.0), p(8.0, 0.0)),
+        PathElement::MoveTo(p(0.0, 0.0)),
+        PathElement::CubicTo(p(0.0, 0.0), p(1.0, 1.0), p(2.0, 0.0)),
+    ]);
+
+    // A half circle arc, with flags written without separators.
+    let arc = elements("M0 0a1 1 0 1010 0");
+    assert_eq!(arc.len(), 3);
+    let path = CGPath::from_elements(arc.clone());
+    let bounds = path.path_bounding_box();
+    assert!(bounds.approx_eq(&CGRect::new(&p(0.0, 0.0), &CGSize::new(10.0, 5.0))));
+    assert_eq!(arc[2].end_point(), Some(p(10.0, 0.0)));
+    // A rotated ellipse, with radii scaled up to reach the end point.
+    let rotated = elements("M0 0A2 1 90 0 1 0 4");
+    assert_eq!(rotated.len(), 3);
+    let bounds = CGPath::from_elements(rotated).path_bounding_box();
+    assert!(bounds.approx_eq(&CGRect::new(&p(0.0, 0.0), &CGSize::new(1.0, 4.0))));
+    assert_eq!(elements("M1 1A0 3 0 0 0 2 2"), vec![PathElement::MoveTo(p(1.0, 1.0)),
+                                                    PathElement::LineTo(p(2.0, 2.0))]);
+
+    let error = |data: &str| match CGPath::from_svg_path_data(data) {
+        Ok(_) => panic!("{:?} parsed", data),
+        Err(error) => error,
+    };
+    assert_eq!(error("L1 1"), SvgPathError::MissingMoveTo);
+    assert_eq!(error("M1 1 X"),
+               SvgPathError::UnexpectedCharacter { offset: 5, character: 'X' });
+    assert_eq!(error("M1"), SvgPathError::InvalidNumber { offset: 2 });
+    assert_eq!(error("M0 0 L1 ."), SvgPathError::InvalidNumber { offset: 8 });
+    assert_eq!(error("M0 0 A1 1 0 2 0 1 1"), SvgPathError::InvalidFlag { offset: 12 });
+    assert_eq!(error("M0 0 Z 1"),
+               SvgPathError::UnexpectedCharacter { offset: 7, character: '1' });
+}
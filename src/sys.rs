@@ -4,9 +4,6 @@ pub type CGImageRef = *mut CGImage;
 pub enum CGColorSpace {}
 pub type CGColorSpaceRef = *mut CGColorSpace;
 
-pub enum CGPath {}
-pub type CGPathRef = *mut CGPath;
-
 pub enum CGDataProvider {}
 pub type CGDataProviderRef = *mut CGDataProvider;
 
@@ -18,6 +15,9 @@ pub type CGContextRef = *mut CGContext;
 
 #[cfg(target_os = "macos")]
 mod macos {
+	pub enum CGPath {}
+	pub type CGPathRef = *mut CGPath;
+
 	pub enum CGEvent {}
 	pub type CGEventRef = *mut CGEvent;
 
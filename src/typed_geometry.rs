@@ -0,0 +1,261 @@
+// Copyright 2018 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Geometry types tagged with the coordinate space they are expressed in.
+//!
+//! The untagged types in `geometry` remain what the rest of the crate takes and returns. These
+//! wrappers are opt-in: converting between spaces has to go through an explicit transform or
+//! flip, so passing e.g. a Cocoa screen point where a global display point is expected fails to
+//! compile.
+
+use base::CGFloat;
+use geometry::{CGAffineTransform, CGPoint, CGRect, CGSize};
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+
+/// The coordinate space that drawing commands on a `CGContext` are expressed in.
+pub enum UserSpace {}
+
+/// The coordinate space of a `CGContext`'s destination, e.g. pixels for a bitmap context.
+pub enum DeviceSpace {}
+
+/// Quartz global display coordinates, as used by `CGDisplay::bounds` and `CGEvent::location`.
+///
+/// The origin is the top-left corner of the main display and the y axis points down.
+pub enum GlobalDisplaySpace {}
+
+/// Cocoa screen coordinates, as used by `NSScreen` and `NSWindow`.
+///
+/// The origin is the bottom-left corner of the main display and the y axis points up.
+pub enum CocoaScreenSpace {}
+
+/// A coordinate space that is the vertical mirror image of another one.
+pub trait FlippedSpace {
+    type Flipped;
+}
+
+impl FlippedSpace for GlobalDisplaySpace {
+    type Flipped = CocoaScreenSpace;
+}
+
+impl FlippedSpace for CocoaScreenSpace {
+    type Flipped = GlobalDisplaySpace;
+}
+
+macro_rules! typed_wrapper {
+    ($name:ident, $untyped:ident) => {
+        #[repr(C)]
+        pub struct $name<Space> {
+            untyped: $untyped,
+            space: PhantomData<Space>,
+        }
+
+        impl<Space> $name<Space> {
+            #[inline]
+            pub fn from_untyped(untyped: &$untyped) -> $name<Space> {
+                $name {
+                    untyped: *untyped,
+                    space: PhantomData,
+                }
+            }
+
+            #[inline]
+            pub fn to_untyped(&self) -> $untyped {
+                self.untyped
+            }
+
+            /// Reinterprets this value as being in another space without converting it.
+            #[inline]
+            pub fn cast_space<NewSpace>(&self) -> $name<NewSpace> {
+                $name::from_untyped(&self.untyped)
+            }
+        }
+
+        impl<Space> Clone for $name<Space> {
+            #[inline]
+            fn clone(&self) -> $name<Space> {
+                *self
+            }
+        }
+
+        impl<Space> Copy for $name<Space> {}
+
+        impl<Space> Debug for $name<Space> {
+            fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+                self.untyped.fmt(formatter)
+            }
+        }
+    }
+}
+
+typed_wrapper!(TypedPoint, CGPoint);
+typed_wrapper!(TypedSize, CGSize);
+typed_wrapper!(TypedRect, CGRect);
+
+impl<Space> TypedPoint<Space> {
+    #[inline]
+    pub fn new(x: CGFloat, y: CGFloat) -> TypedPoint<Space> {
+        TypedPoint::from_untyped(&CGPoint::new(x, y))
+    }
+
+    /// Mirrors this point vertically within a space of the given height, e.g. the height of the
+    /// main display when converting between global display and Cocoa screen coordinates.
+    #[inline]
+    pub fn flip(&self, height: CGFloat) -> TypedPoint<Space::Flipped> where Space: FlippedSpace {
+        TypedPoint::new(self.untyped.x, height - self.untyped.y)
+    }
+}
+
+impl<Space> TypedSize<Space> {
+    #[inline]
+    pub fn new(width: CGFloat, height: CGFloat) -> TypedSize<Space> {
+        TypedSize::from_untyped(&CGSize::new(width, height))
+    }
+
+    /// Sizes are unaffected by flipping; this only changes the space.
+    #[inline]
+    pub fn flip(&self) -> TypedSize<Space::Flipped> where Space: FlippedSpace {
+        self.cast_space()
+    }
+}
+
+impl<Space> TypedRect<Space> {
+    #[inline]
+    pub fn new(origin: &TypedPoint<Space>, size: &TypedSize<Space>) -> TypedRect<Space> {
+        TypedRect::from_untyped(&CGRect::new(&origin.untyped, &size.untyped))
+    }
+
+    #[inline]
+    pub fn origin(&self) -> TypedPoint<Space> {
+        TypedPoint::from_untyped(&self.untyped.origin)
+    }
+
+    #[inline]
+    pub fn size(&self) -> TypedSize<Space> {
+        TypedSize::from_untyped(&self.untyped.size)
+    }
+
+    /// Mirrors this rectangle vertically within a space of the given height. The result is
+    /// standardized, so its origin is again the corner with the smallest coordinates.
+    pub fn flip(&self, height: CGFloat) -> TypedRect<Space::Flipped> where Space: FlippedSpace {
+        if self.untyped.is_null() {
+            return self.cast_space();
+        }
+
+        let rect = self.untyped.standardize();
+        let origin = CGPoint::new(rect.origin.x, height - (rect.origin.y + rect.size.height));
+        TypedRect::from_untyped(&CGRect::new(&origin, &rect.size))
+    }
+}
+
+/// An affine transform from points in the `Src` space to points in the `Dst` space.
+#[repr(C)]
+pub struct TypedTransform<Src, Dst> {
+    untyped: CGAffineTransform,
+    spaces: PhantomData<(Src, Dst)>,
+}
+
+impl<Src, Dst> TypedTransform<Src, Dst> {
+    #[inline]
+    pub fn from_untyped(untyped: &CGAffineTransform) -> TypedTransform<Src, Dst> {
+        TypedTransform {
+            untyped: *untyped,
+            spaces: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn to_untyped(&self) -> CGAffineTransform {
+        self.untyped
+    }
+
+    #[inline]
+    pub fn transform_point(&self, point: &TypedPoint<Src>) -> TypedPoint<Dst> {
+        TypedPoint::from_untyped(&point.untyped.apply_transform(&self.untyped))
+    }
+
+    #[inline]
+    pub fn transform_size(&self, size: &TypedSize<Src>) -> TypedSize<Dst> {
+        TypedSize::from_untyped(&size.untyped.apply_transform(&self.untyped))
+    }
+
+    #[inline]
+    pub fn transform_rect(&self, rect: &TypedRect<Src>) -> TypedRect<Dst> {
+        TypedRect::from_untyped(&rect.untyped.apply_transform(&self.untyped))
+    }
+
+    /// Returns this transform followed by `other`.
+    #[inline]
+    pub fn then<NewDst>(&self, other: &TypedTransform<Dst, NewDst>) -> TypedTransform<Src, NewDst> {
+        TypedTransform::from_untyped(&self.untyped.concat(&other.untyped))
+    }
+
+    /// Returns the inverse transform, or `None` if this transform is not invertible.
+    #[inline]
+    pub fn inverse(&self) -> Option<TypedTransform<Dst, Src>> {
+        self.untyped.checked_invert().map(|t| TypedTransform::from_untyped(&t))
+    }
+}
+
+impl<Space: FlippedSpace> TypedTransform<Space, Space::Flipped> {
+    /// Returns the transform that mirrors points vertically within a space of the given height.
+    #[inline]
+    pub fn flip(height: CGFloat) -> TypedTransform<Space, Space::Flipped> {
+        TypedTransform::from_untyped(&CGAffineTransform::new(1.0, 0.0, 0.0, -1.0, 0.0, height))
+    }
+}
+
+impl<Src, Dst> Clone for TypedTransform<Src, Dst> {
+    #[inline]
+    fn clone(&self) -> TypedTransform<Src, Dst> {
+        *self
+    }
+}
+
+impl<Src, Dst> Copy for TypedTransform<Src, Dst> {}
+
+impl<Src, Dst> Debug for TypedTransform<Src, Dst> {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        self.untyped.fmt(formatter)
+    }
+}
+
+#[test]
+fn flip_between_display_spaces() {
+    let main_height = 900.0;
+    let point: TypedPoint<GlobalDisplaySpace> = TypedPoint::new(10.0, 100.0);
+    let cocoa: TypedPoint<CocoaScreenSpace> = point.flip(main_height);
+    assert_eq!((cocoa.to_untyped().x, cocoa.to_untyped().y), (10.0, 800.0));
+    assert_eq!(cocoa.flip(main_height).to_untyped().y, 100.0);
+
+    let rect: TypedRect<GlobalDisplaySpace> = TypedRect::new(&point, &TypedSize::new(50.0, 200.0));
+    let cocoa = rect.flip(main_height).to_untyped();
+    assert_eq!((cocoa.origin.x, cocoa.origin.y), (10.0, 600.0));
+    assert_eq!((cocoa.size.width, cocoa.size.height), (50.0, 200.0));
+
+    let flip: TypedTransform<GlobalDisplaySpace, CocoaScreenSpace> =
+        TypedTransform::flip(main_height);
+    let via_transform = flip.transform_rect(&rect).to_untyped();
+    assert_eq!((via_transform.origin.y, via_transform.size.height), (600.0, 200.0));
+}
+
+#[test]
+fn typed_transform_composition() {
+    let user_to_device: TypedTransform<UserSpace, DeviceSpace> =
+        TypedTransform::from_untyped(&CGAffineTransform::make_scale(2.0, 2.0));
+    let device_to_cocoa: TypedTransform<DeviceSpace, CocoaScreenSpace> =
+        TypedTransform::from_untyped(&CGAffineTransform::make_translation(100.0, 50.0));
+    let user_to_cocoa = user_to_device.then(&device_to_cocoa);
+
+    let p = user_to_cocoa.transform_point(&TypedPoint::new(1.0, 2.0)).to_untyped();
+    assert_eq!((p.x, p.y), (102.0, 54.0));
+
+    let back = user_to_cocoa.inverse().unwrap().transform_point(&TypedPoint::new(102.0, 54.0));
+    assert_eq!((back.to_untyped().x, back.to_untyped().y), (1.0, 2.0));
+}